@@ -5,11 +5,19 @@ pub trait ALU {
     type Data;
     /// if operation is unary, lhs won't be used.
     fn op(&self, lhs: Self::Data, rhs: Self::Data) -> (Self::Data, EnumSet<Self::Flag>);
+    /// flags the operation may change. the others should be left as they are.
+    fn affected_flags(&self) -> EnumSet<Self::Flag> {
+        EnumSet::all()
+    }
+    /// mnemonic of the operation, e.g. `"ADD"`.
+    fn name(&self) -> &'static str;
 }
 
 pub trait Flag: Sized + Copy + EnumSetType {}
 
+/// with the `serde` feature, an `EnumSet` of these serializes as its bitmask.
 #[derive(Debug, EnumSetType)]
+#[cfg_attr(feature = "serde", enumset(serialize_repr = "u8"))]
 pub enum StatusFlag {
     /// result is zero.
     Zero,
@@ -32,7 +40,7 @@ impl StatusFlag {
         if result >= 0x80 {
             flag_set |= Self::Sign;
         }
-        if result.count_ones() % 2 == 0 {
+        if result.count_ones().is_multiple_of(2) {
             flag_set |= Self::Parity;
         }
         flag_set
@@ -74,6 +82,7 @@ pub mod bit8 {
     }
 
     impl Adder {
+        #[allow(clippy::self_named_constructors)]
         pub fn adder() -> Self {
             Self::default()
         }
@@ -95,6 +104,14 @@ pub mod bit8 {
                 cin: false,
             }
         }
+        /// `op` and whether the result overflowed as signed, i.e. the Z80's P/V after
+        /// arithmetic. the 8080 has no such flag.
+        pub fn op_with_overflow(&self, lhs: u8, rhs: u8) -> (u8, EnumSet<StatusFlag>, bool) {
+            let (res, status) = self.op(lhs, rhs);
+            let rhs = if self.neg { !rhs } else { rhs };
+            let overflow = (lhs ^ res) & (rhs ^ res) & 0x80 != 0;
+            (res, status, overflow)
+        }
     }
 
     impl ALU for Adder {
@@ -107,11 +124,21 @@ pub mod bit8 {
             }
             let (res, mut status) = primary_adder(self.cin, lhs, rhs);
             if self.neg {
+                // carry means borrow, though auxiliary carry stays the carry out of bit 3.
                 status ^= StatusFlag::Carry;
-                status ^= StatusFlag::AuxiliaryCarry;
             }
             (res, status)
         }
+
+        /// named after the arithmetic done, so `ADC` without carry is `"ADD"`.
+        fn name(&self) -> &'static str {
+            match (self.neg, self.cin) {
+                (false, false) => "ADD",
+                (false, true) => "ADC",
+                (true, true) => "SUB",
+                (true, false) => "SBB",
+            }
+        }
     }
 
     #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -125,16 +152,29 @@ pub mod bit8 {
         type Data = u8;
 
         fn op(&self, _lhs: Self::Data, rhs: Self::Data) -> (Self::Data, EnumSet<Self::Flag>) {
-            let (res, carry) = match self {
-                IncDecOperator::Increase => rhs.overflowing_add(1),
-                IncDecOperator::Decrease => rhs.overflowing_sub(1),
+            // auxiliary carry is the carry out of bit 3 of `rhs + 1` or `rhs + 0xff`.
+            let (res, auxiliary_carry) = match self {
+                IncDecOperator::Increase => (rhs.wrapping_add(1), rhs & 0xf == 0xf),
+                IncDecOperator::Decrease => (rhs.wrapping_sub(1), rhs & 0xf != 0),
             };
             let mut status = StatusFlag::set_by_result(res);
-            if carry {
-                status |= StatusFlag::Carry;
+            if auxiliary_carry {
+                status |= StatusFlag::AuxiliaryCarry;
             }
             (res, status)
         }
+
+        /// `INR` and `DCR` leave carry as it is.
+        fn affected_flags(&self) -> EnumSet<Self::Flag> {
+            !StatusFlag::Carry
+        }
+
+        fn name(&self) -> &'static str {
+            match self {
+                IncDecOperator::Increase => "INR",
+                IncDecOperator::Decrease => "DCR",
+            }
+        }
     }
 
     #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -156,7 +196,29 @@ pub mod bit8 {
                 LogicalOperator::Not => !rhs,
                 LogicalOperator::Xor => lhs ^ rhs,
             };
-            (res, StatusFlag::set_by_result(res))
+            let mut status = StatusFlag::set_by_result(res);
+            // `ANA` reflects bit 3 of either operand into auxiliary carry.
+            if *self == LogicalOperator::And && (lhs | rhs) & 0x08 != 0 {
+                status |= StatusFlag::AuxiliaryCarry;
+            }
+            (res, status)
+        }
+
+        /// `CMA` changes no flags.
+        fn affected_flags(&self) -> EnumSet<Self::Flag> {
+            match self {
+                LogicalOperator::Not => EnumSet::empty(),
+                _ => EnumSet::all(),
+            }
+        }
+
+        fn name(&self) -> &'static str {
+            match self {
+                LogicalOperator::And => "ANA",
+                LogicalOperator::Or => "ORA",
+                LogicalOperator::Not => "CMA",
+                LogicalOperator::Xor => "XRA",
+            }
         }
     }
 
@@ -224,6 +286,20 @@ pub mod bit8 {
             }
             (res, status)
         }
+
+        /// rotations change carry only.
+        fn affected_flags(&self) -> EnumSet<Self::Flag> {
+            StatusFlag::Carry.into()
+        }
+
+        fn name(&self) -> &'static str {
+            match (self.through_carry, self.rotate_right) {
+                (false, false) => "RLC",
+                (false, true) => "RRC",
+                (true, false) => "RAL",
+                (true, true) => "RAR",
+            }
+        }
     }
 
     #[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
@@ -268,11 +344,157 @@ pub mod bit8 {
             }
             (res, status)
         }
+
+        fn name(&self) -> &'static str {
+            "DAA"
+        }
+    }
+
+    /// add `carry_in` to the little-endian packed BCD number `digits` and decimal adjust
+    /// each byte as `ACI 0; DAA` would, passing the carry on. returns the carry out.
+    pub fn daa_multibyte(digits: &mut [u8], carry_in: bool) -> bool {
+        bcd_add(digits, &[], carry_in)
+    }
+
+    /// add little-endian packed BCD numbers as a chain of `ADC` and `DAA` does, leaving the
+    /// sum in `lhs`. `rhs` missing upper digits counts as 0. returns the carry out.
+    pub fn bcd_add(lhs: &mut [u8], rhs: &[u8], carry_in: bool) -> bool {
+        lhs.iter_mut()
+            .enumerate()
+            .fold(carry_in, |carry, (i, digit)| {
+                let rhs = rhs.get(i).copied().unwrap_or_default();
+                let (sum, status) = Adder {
+                    neg: false,
+                    cin: carry,
+                }
+                .op(*digit, rhs);
+                let (res, status) = DecimalAdjuster::from_status(status).op(0, sum);
+                *digit = res;
+                status.contains(StatusFlag::Carry)
+            })
     }
     #[cfg(test)]
     mod tests {
         use super::*;
 
+        #[test]
+        fn name() {
+            let names = [
+                Adder::adder().name(),
+                Adder::carried_adder().name(),
+                Adder::subber().name(),
+                Adder::borrowed_subber().name(),
+                IncDecOperator::Increase.name(),
+                IncDecOperator::Decrease.name(),
+                LogicalOperator::And.name(),
+                LogicalOperator::Or.name(),
+                LogicalOperator::Not.name(),
+                LogicalOperator::Xor.name(),
+                Rotator::rotate_left().name(),
+                Rotator::rotate_right().name(),
+                Rotator::rotate_left().through_carry().name(),
+                Rotator::rotate_right().through_carry().name(),
+                DecimalAdjuster::default().name(),
+            ];
+            assert_eq!(
+                names,
+                [
+                    "ADD", "ADC", "SUB", "SBB", "INR", "DCR", "ANA", "ORA", "CMA", "XRA", "RLC",
+                    "RRC", "RAL", "RAR", "DAA"
+                ]
+            );
+        }
+
+        #[test]
+        fn bcd_multibyte() {
+            // 12345678 + 87654321
+            let mut sum = [0x78, 0x56, 0x34, 0x12];
+            assert!(!bcd_add(&mut sum, &[0x21, 0x43, 0x65, 0x87], false));
+            assert_eq!(sum, [0x99, 0x99, 0x99, 0x99]);
+            // 12345678 + 87654329
+            let mut sum = [0x78, 0x56, 0x34, 0x12];
+            assert!(bcd_add(&mut sum, &[0x29, 0x43, 0x65, 0x87], false));
+            assert_eq!(sum, [0x07, 0x00, 0x00, 0x00]);
+            // 99999999 + 1
+            let mut sum = [0x99; 4];
+            assert!(bcd_add(&mut sum, &[0x01], false));
+            assert_eq!(sum, [0x00; 4]);
+            // 19 + 28 + 1
+            let mut sum = [0x19, 0x00];
+            assert!(!bcd_add(&mut sum, &[0x28], true));
+            assert_eq!(sum, [0x48, 0x00]);
+
+            let mut counter = [0x99, 0x09];
+            assert!(!daa_multibyte(&mut counter, true));
+            assert_eq!(counter, [0x00, 0x10]);
+            let mut counter = [0x99, 0x99];
+            assert!(daa_multibyte(&mut counter, true));
+            assert_eq!(counter, [0x00, 0x00]);
+            let mut counter = [0x0a, 0x00];
+            assert!(!daa_multibyte(&mut counter, false));
+            assert_eq!(counter, [0x10, 0x00]);
+        }
+
+        #[test]
+        fn adc_half_carry() {
+            use StatusFlag::*;
+            assert_eq!(
+                Adder::carried_adder().op(0x0f, 0x00),
+                (0x10, AuxiliaryCarry.into())
+            );
+            assert_eq!(Adder::adder().op(0x0f, 0x00), (0x0f, Parity.into()));
+            assert_eq!(
+                Adder::carried_adder().op(0x0e, 0x01),
+                (0x10, AuxiliaryCarry.into())
+            );
+            assert_eq!(Adder::carried_adder().op(0x0e, 0x00), (0x0f, Parity.into()));
+        }
+
+        #[test]
+        fn sub_half_carry() {
+            use StatusFlag::*;
+            // the 8080 sets AC when the low nibble does *not* borrow, unlike the Z80.
+            let cases = [
+                // SUB A of 0x3e, from the Intel manual
+                (
+                    Adder::subber(),
+                    0x3e,
+                    0x3e,
+                    0x00,
+                    Zero | Parity | AuxiliaryCarry,
+                ),
+                // CMP E of 0x0a and 0x05, from the Intel manual
+                (Adder::subber(), 0x0a, 0x05, 0x05, Parity | AuxiliaryCarry),
+                // SBB L of 0x04 and 0x02 with carry, from the Intel manual
+                (
+                    Adder::borrowed_subber(),
+                    0x04,
+                    0x02,
+                    0x01,
+                    AuxiliaryCarry.into(),
+                ),
+                (Adder::subber(), 0x00, 0x01, 0xff, Sign | Parity | Carry),
+                (Adder::subber(), 0x10, 0x01, 0x0f, Parity.into()),
+                (Adder::subber(), 0x13, 0x02, 0x11, Parity | AuxiliaryCarry),
+                (Adder::borrowed_subber(), 0x13, 0x03, 0x0f, Parity.into()),
+                (
+                    Adder::borrowed_subber(),
+                    0x00,
+                    0x00,
+                    0xff,
+                    Sign | Parity | Carry,
+                ),
+            ];
+            for (subber, lhs, rhs, res, status) in cases {
+                let name = subber.name();
+                assert_eq!(
+                    subber.op(lhs, rhs),
+                    (res, status),
+                    "{name} {lhs:#04x} {rhs:#04x}"
+                );
+            }
+        }
+
         #[test]
         fn alu() {
             use StatusFlag::*;
@@ -292,11 +514,11 @@ pub mod bit8 {
             );
             assert_eq!(c_adder.op(6, 9), (16, AuxiliaryCarry.into()));
             let subber = Adder::subber();
-            assert_eq!(subber.op(18, 3), (15, Parity | AuxiliaryCarry));
-            assert_eq!(subber.op(16, 19), (253, Sign | Carry | AuxiliaryCarry));
-            assert_eq!(subber.op(9, 9), (0, Zero | Parity));
+            assert_eq!(subber.op(18, 3), (15, Parity.into()));
+            assert_eq!(subber.op(16, 19), (253, Sign | Carry));
+            assert_eq!(subber.op(9, 9), (0, Zero | Parity | AuxiliaryCarry));
             let b_subber = Adder::borrowed_subber();
-            assert_eq!(b_subber.op(10, 3), (6, Parity.into()));
+            assert_eq!(b_subber.op(10, 3), (6, Parity | AuxiliaryCarry));
             let alus: Vec<Box<dyn ALU<Flag = StatusFlag, Data = u8>>> = vec![
                 (Box::new(Adder::adder())),
                 (Box::new(Adder::subber())),
@@ -311,6 +533,79 @@ pub mod bit8 {
             );
         }
 
+        #[test]
+        fn sign_boundary() {
+            for (res, sign) in [(0x00, false), (0x7f, false), (0x80, true), (0xff, true)] {
+                assert_eq!(
+                    StatusFlag::set_by_result(res).contains(StatusFlag::Sign),
+                    sign
+                );
+                let (r, status) = Adder::adder().op(res, 0);
+                assert_eq!((r, status.contains(StatusFlag::Sign)), (res, sign));
+                let (r, status) = IncDecOperator::Increase.op(0, res.wrapping_sub(1));
+                assert_eq!((r, status.contains(StatusFlag::Sign)), (res, sign));
+                let (r, status) = IncDecOperator::Decrease.op(0, res.wrapping_add(1));
+                assert_eq!((r, status.contains(StatusFlag::Sign)), (res, sign));
+                let (r, status) = LogicalOperator::Or.op(res, 0);
+                assert_eq!((r, status.contains(StatusFlag::Sign)), (res, sign));
+                let (r, status) = LogicalOperator::And.op(res, 0xff);
+                assert_eq!((r, status.contains(StatusFlag::Sign)), (res, sign));
+            }
+        }
+
+        /// run each row of a truth table fixture, and returns the mismatching ones.
+        fn fixture_mismatches(fixture: &str) -> Vec<String> {
+            use StatusFlag::*;
+            let letters = |s: &str| {
+                s.chars()
+                    .filter_map(|c| match c {
+                        'S' => Some(Sign),
+                        'Z' => Some(Zero),
+                        'A' => Some(AuxiliaryCarry),
+                        'P' => Some(Parity),
+                        'C' => Some(Carry),
+                        '-' => None,
+                        other => panic!("unknown flag {other}"),
+                    })
+                    .collect::<EnumSet<_>>()
+            };
+            let hex = |s: &str| u8::from_str_radix(s, 16).unwrap();
+            fixture
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+                .filter_map(|(i, line)| {
+                    let [op, lhs, rhs, flags_in, result, flags_out] =
+                        line.split(',').collect::<Vec<_>>()[..]
+                    else {
+                        return Some(format!("line {}: malformed `{line}`", i + 1));
+                    };
+                    let flags_in = letters(flags_in);
+                    let carried = flags_in.contains(Carry);
+                    let alu: Box<dyn ALU<Flag = StatusFlag, Data = u8>> = match op {
+                        "ADD" => Box::new(Adder::adder()),
+                        "ADC" if carried => Box::new(Adder::carried_adder()),
+                        "ADC" => Box::new(Adder::adder()),
+                        "SUB" => Box::new(Adder::subber()),
+                        "SBB" if carried => Box::new(Adder::borrowed_subber()),
+                        "SBB" => Box::new(Adder::subber()),
+                        "DAA" => Box::new(DecimalAdjuster::from_status(flags_in)),
+                        other => return Some(format!("line {}: unknown op {other}", i + 1)),
+                    };
+                    let actual = alu.op(hex(lhs), hex(rhs));
+                    let expected = (hex(result), letters(flags_out));
+                    (actual != expected)
+                        .then(|| format!("line {}: `{line}` got {actual:02x?}", i + 1))
+                })
+                .collect()
+        }
+
+        #[test]
+        fn truth_table() {
+            let mismatches = fixture_mismatches(include_str!("../fixtures/alu.csv"));
+            assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+        }
+
         #[test]
         fn daa() {
             for lhs in 0..=255 {
@@ -329,6 +624,109 @@ pub mod bit8 {
                     }
                 }
             }
+            // every input against the correction byte the 8080 adds
+            for acc in 0..=255u8 {
+                for (carry, auxiliary) in
+                    [(false, false), (false, true), (true, false), (true, true)]
+                {
+                    let (lsd, msd) = (acc & 0xf, acc >> 4);
+                    let mut correction = 0;
+                    if auxiliary || lsd > 9 {
+                        correction |= 0x06;
+                    }
+                    let carry_out = carry || msd > 9 || (msd >= 9 && lsd > 9);
+                    if carry_out {
+                        correction |= 0x60;
+                    }
+                    let res = acc.wrapping_add(correction);
+                    let mut expected = StatusFlag::set_by_result(res);
+                    if carry_out {
+                        expected |= StatusFlag::Carry;
+                    }
+                    if lsd + (correction & 0xf) > 0xf {
+                        expected |= StatusFlag::AuxiliaryCarry;
+                    }
+                    let adjuster = DecimalAdjuster { carry, auxiliary };
+                    assert_eq!(
+                        adjuster.op(0, acc),
+                        (res, expected),
+                        "{acc:02x} {carry} {auxiliary}"
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn signed_overflow() {
+            let overflow = |adder: Adder, lhs, rhs| adder.op_with_overflow(lhs, rhs).2;
+            assert!(overflow(Adder::adder(), 0x7f, 0x01));
+            assert!(overflow(Adder::adder(), 0x80, 0x80));
+            assert!(!overflow(Adder::adder(), 0x7e, 0x01));
+            assert!(!overflow(Adder::adder(), 0xff, 0x01));
+            assert!(!overflow(Adder::adder(), 0x80, 0x7f));
+            assert!(overflow(Adder::carried_adder(), 0x7f, 0x00));
+            assert!(overflow(Adder::subber(), 0x80, 0x01));
+            assert!(overflow(Adder::subber(), 0x7f, 0xff));
+            assert!(!overflow(Adder::subber(), 0x00, 0x01));
+            assert!(overflow(Adder::borrowed_subber(), 0x80, 0x00));
+            let (res, status, _) = Adder::adder().op_with_overflow(0x7f, 0x01);
+            assert_eq!((res, status), Adder::adder().op(0x7f, 0x01));
+        }
+
+        #[test]
+        fn inc_dec_half_carry() {
+            use IncDecOperator::*;
+            use StatusFlag::*;
+            assert_eq!(Increase.op(0, 0x0f), (0x10, AuxiliaryCarry.into()));
+            assert_eq!(Increase.op(0, 0x0e), (0x0f, Parity.into()));
+            // decrement adds 0xff, so AC is set unless the low nibble borrows.
+            assert_eq!(Decrease.op(0, 0x10), (0x0f, Parity.into()));
+            assert_eq!(Decrease.op(0, 0x11), (0x10, AuxiliaryCarry.into()));
+            assert_eq!(Decrease.op(0, 0x00), (0xff, Sign | Parity));
+        }
+
+        #[test]
+        fn flags_by_category() {
+            use StatusFlag::*;
+            // Z, S and P follow the result, i.e. `StatusFlag::set_by_result`.
+            assert_eq!(
+                IncDecOperator::Increase.op(0, 0xff),
+                (0x00, Zero | Parity | AuxiliaryCarry)
+            );
+            assert_eq!(
+                IncDecOperator::Decrease.op(0, 0x80),
+                (0x7f, EnumSet::empty())
+            );
+            assert_eq!(LogicalOperator::Or.op(0x80, 0x01), (0x81, Sign | Parity));
+            assert_eq!(LogicalOperator::Xor.op(0x5a, 0x5a), (0x00, Zero | Parity));
+            assert_eq!(
+                LogicalOperator::And.op(0xf0, 0x0f),
+                (0x00, Zero | Parity | AuxiliaryCarry)
+            );
+            assert_eq!(
+                DecimalAdjuster::default().op(0, 0x9b),
+                (0x01, Carry | AuxiliaryCarry)
+            );
+            assert_eq!(
+                DecimalAdjuster::default().op(0, 0x9a),
+                (0x00, Zero | Parity | Carry | AuxiliaryCarry)
+            );
+            // and only these ops leave some of them alone
+            assert_eq!(IncDecOperator::Increase.affected_flags(), !Carry);
+            assert_eq!(Rotator::rotate_left().affected_flags(), Carry);
+            assert_eq!(
+                Rotator::rotate_right().through_carry().op(0, 0x01),
+                (0x00, Carry.into())
+            );
+            assert!(LogicalOperator::Not.affected_flags().is_empty());
+            for alu in [
+                LogicalOperator::And,
+                LogicalOperator::Or,
+                LogicalOperator::Xor,
+            ] {
+                assert_eq!(alu.affected_flags(), EnumSet::all());
+            }
+            assert_eq!(DecimalAdjuster::default().affected_flags(), EnumSet::all());
         }
 
         #[test]
@@ -366,5 +764,38 @@ pub mod bit8 {
                 (0b0001_0000, StatusFlag::Carry.into())
             );
         }
+
+        #[test]
+        fn rotate_exhaustive() {
+            let flags = |carry: bool| match carry {
+                true => StatusFlag::Carry.into(),
+                false => EnumSet::empty(),
+            };
+            for x in 0..=255u8 {
+                for cin in [false, true] {
+                    let (msb, lsb) = (x & 0x80 != 0, x & 0x01 != 0);
+                    let cases = [
+                        // RLC, RRC, RAL, RAR
+                        (Rotator::rotate_left(), x.rotate_left(1), msb),
+                        (Rotator::rotate_right(), x.rotate_right(1), lsb),
+                        (
+                            Rotator::rotate_left().through_carry(),
+                            x << 1 | cin as u8,
+                            msb,
+                        ),
+                        (
+                            Rotator::rotate_right().through_carry(),
+                            x >> 1 | (cin as u8) << 7,
+                            lsb,
+                        ),
+                    ];
+                    for (rotator, result, carry) in cases {
+                        let rotator = rotator.carried(cin);
+                        let name = format!("{} {x:#04x} carry {cin}", rotator.name());
+                        assert_eq!(rotator.op(0, x), (result, flags(carry)), "{name}");
+                    }
+                }
+            }
+        }
     }
 }
@@ -1,4 +1,4 @@
-use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
 
 pub trait BitsSignal:
     BitAnd<Output = Self>
@@ -25,3 +25,22 @@ macro_rules! bitwise_ops_impl {
 }
 
 bitwise_ops_impl!(u8 u16 u32 u64 usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear_masked<T: BitsSignal>(bits: T, mask: T) -> T {
+        let mut bits = bits;
+        bits &= !mask;
+        bits
+    }
+
+    #[test]
+    fn bits_signal() {
+        assert_eq!(clear_masked(0xffu8, 0x0f), 0xf0);
+        assert_eq!(clear_masked(0xbeefu16, 0xff00), 0x00ef);
+        assert_eq!(clear_masked(u64::MAX, 1), u64::MAX - 1);
+        assert_eq!(clear_masked(6usize, 2), 4);
+    }
+}
@@ -1,15 +1,55 @@
-pub trait DataBus {
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Carry a value of `T`, whichever it is data or address.
+pub trait Bus<T> {
+    fn set(&mut self, value: T);
+    fn get(&self) -> T;
+}
+
+/// Bus used for data.
+pub trait DataBus: Bus<Self::Data> {
     type Data;
-    fn set_data(&mut self, data: Self::Data);
-    fn get_data(&self) -> Self::Data;
+    fn set_data(&mut self, data: Self::Data) {
+        self.set(data)
+    }
+    fn get_data(&self) -> Self::Data {
+        self.get()
+    }
 }
 
-pub trait AddressBus {
+/// Bus used for address.
+pub trait AddressBus: Bus<Self::Address> {
     type Address;
-    fn set_address(&mut self, data: Self::Address);
-    fn get_address(&self) -> Self::Address;
+    fn set_address(&mut self, data: Self::Address) {
+        self.set(data)
+    }
+    fn get_address(&self) -> Self::Address {
+        self.get()
+    }
+}
+
+/// A wire shared among components.
+impl<T: Copy> Bus<T> for Rc<Cell<T>> {
+    fn set(&mut self, value: T) {
+        Cell::set(self, value)
+    }
+    fn get(&self) -> T {
+        Cell::get(self)
+    }
+}
+
+impl<T: Copy> DataBus for Rc<Cell<T>> {
+    type Data = T;
 }
 
+impl<T: Copy> AddressBus for Rc<Cell<T>> {
+    type Address = T;
+}
+
+/// A control line shared among components, asserted when true.
+pub type Signal = Rc<Cell<bool>>;
+
 /// Be connected to data bus and can load data from it.
 pub trait DataBusLoad {
     type DataBus: DataBus;
@@ -33,3 +73,30 @@ pub trait AddressBusRead {
     type AddressBus: AddressBus;
     fn read_address(&self);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latch<T: Copy, B: Bus<T>>(bus: &mut B, value: T) -> T {
+        bus.set(value);
+        bus.get()
+    }
+
+    #[test]
+    fn unified_bus() {
+        let mut data = 0u8;
+        let mut address = 0u16;
+        assert_eq!(latch(&mut data, 0x12), 0x12);
+        assert_eq!(latch(&mut address, 0x3456), 0x3456);
+        assert_eq!(data.get_data(), 0x12);
+        assert_eq!(address.get_address(), 0x3456);
+
+        let data = Rc::new(Cell::new(0u8));
+        let address = Rc::new(Cell::new(0u16));
+        assert_eq!(latch(&mut Rc::clone(&data), 0x78), 0x78);
+        assert_eq!(latch(&mut Rc::clone(&address), 0x9abc), 0x9abc);
+        assert_eq!(data.get_data(), 0x78);
+        assert_eq!(address.get_address(), 0x9abc);
+    }
+}
@@ -0,0 +1,21 @@
+use std::fmt::Debug;
+
+/// paces an emulated processor against time.
+pub trait Clock: Debug {
+    /// called after each instruction or interrupt with the T-states it took.
+    fn wait(&mut self, states: u64);
+}
+
+/// records the waits instead of sleeping, for tests to examine the cycle schedule.
+#[cfg(test)]
+#[derive(Debug, Default, Clone)]
+pub struct FakeClock {
+    pub waits: std::rc::Rc<std::cell::RefCell<Vec<u64>>>,
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn wait(&mut self, states: u64) {
+        self.waits.borrow_mut().push(states)
+    }
+}
@@ -0,0 +1,272 @@
+/// split an opcode into its `op`, `dst` and `src` fields of 2, 3 and 3 bits.
+pub(crate) fn decompose(opcode: u8) -> (u8, u8, u8) {
+    (opcode >> 6, (opcode >> 3) & 0x7, opcode & 0x7)
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum I8080JumpCondition {
+    Anytime,
+    OnNonZero,
+    OnZero,
+    OnNonCarry,
+    OnCarry,
+    OnParityOdd,
+    OnParityEven,
+    OnPlus,
+    OnMinus,
+}
+
+/// an 8-bit register an instruction names, numbered as in its opcode.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Reg8 {
+    B = 0,
+    C = 1,
+    D = 2,
+    E = 3,
+    H = 4,
+    L = 5,
+    A = 7,
+}
+
+/// an 8-bit operand, a register or `M`, the memory HL points.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operand {
+    Reg(Reg8),
+    M,
+}
+
+/// a register pair of `LXI`, `INX`, `DCX` and `DAD`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Pair {
+    BC = 0,
+    DE = 1,
+    HL = 2,
+    SP = 3,
+}
+
+/// a register pair of `PUSH` and `POP`, with `PSW` in place of `SP`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StackPair {
+    BC = 0,
+    DE = 1,
+    HL = 2,
+    PSW = 3,
+}
+
+/// a register pair `STAX` and `LDAX` address memory through.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IndirectPair {
+    BC = 0,
+    DE = 1,
+}
+
+/// a decoded instruction with its operands. unconditional jumps, calls and returns
+/// have the condition `Anytime`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Instruction {
+    Nop,
+    Lxi(Pair, u16),
+    Stax(IndirectPair),
+    Ldax(IndirectPair),
+    Shld(u16),
+    Lhld(u16),
+    Sta(u16),
+    Lda(u16),
+    Inx(Pair),
+    Dcx(Pair),
+    Dad(Pair),
+    Inr(Operand),
+    Dcr(Operand),
+    Mvi(Operand, u8),
+    Rlc,
+    Rrc,
+    Ral,
+    Rar,
+    Daa,
+    Cma,
+    Stc,
+    Cmc,
+    /// 8085 only, `NOP` on the 8080.
+    Rim,
+    /// 8085 only, `NOP` on the 8080.
+    Sim,
+    Mov(Operand, Operand),
+    Hlt,
+    Add(Operand),
+    Adc(Operand),
+    Sub(Operand),
+    Sbb(Operand),
+    Ana(Operand),
+    Xra(Operand),
+    Ora(Operand),
+    Cmp(Operand),
+    Adi(u8),
+    Aci(u8),
+    Sui(u8),
+    Sbi(u8),
+    Ani(u8),
+    Xri(u8),
+    Ori(u8),
+    Cpi(u8),
+    Ret(I8080JumpCondition),
+    Jmp(I8080JumpCondition, u16),
+    Call(I8080JumpCondition, u16),
+    Pop(StackPair),
+    Push(StackPair),
+    Pchl,
+    Sphl,
+    Out(u8),
+    In(u8),
+    Xthl,
+    Xchg,
+    Di,
+    Ei,
+    /// `RST n`, taken modulo 8.
+    Rst(u8),
+}
+
+impl Operand {
+    fn from_bits(bits: u8) -> Self {
+        use Reg8::*;
+        match bits {
+            6 => Operand::M,
+            _ => Operand::Reg([B, C, D, E, H, L, A, A][bits as usize]),
+        }
+    }
+    #[cfg(feature = "std")]
+    pub(crate) fn bits(self) -> u8 {
+        match self {
+            Operand::Reg(reg) => reg as u8,
+            Operand::M => 6,
+        }
+    }
+}
+
+fn condition(bits: u8) -> I8080JumpCondition {
+    use I8080JumpCondition::*;
+    [
+        OnNonZero,
+        OnZero,
+        OnNonCarry,
+        OnCarry,
+        OnParityOdd,
+        OnParityEven,
+        OnPlus,
+        OnMinus,
+    ][bits as usize]
+}
+
+impl I8080JumpCondition {
+    /// the `ccc` field of the opcode, or `None` for `Anytime`.
+    #[cfg(feature = "std")]
+    pub(crate) fn bits(self) -> Option<u8> {
+        use I8080JumpCondition::*;
+        let bits = match self {
+            Anytime => return None,
+            OnNonZero => 0,
+            OnZero => 1,
+            OnNonCarry => 2,
+            OnCarry => 3,
+            OnParityOdd => 4,
+            OnParityEven => 5,
+            OnPlus => 6,
+            OnMinus => 7,
+        };
+        Some(bits)
+    }
+}
+
+/// decode the instruction at the head of `bytes`, and its length, or `None` if `bytes` is
+/// empty. missing operand bytes read as 0. undocumented opcodes decode as the instruction
+/// they behave as, and 0x20 and 0x30 as `RIM` and `SIM`.
+pub fn decode(bytes: &[u8]) -> Option<(Instruction, usize)> {
+    use I8080JumpCondition::Anytime;
+    use Instruction::*;
+    let &opcode = bytes.first()?;
+    let byte = bytes.get(1).copied().unwrap_or_default();
+    let word = u16::from_le_bytes([byte, bytes.get(2).copied().unwrap_or_default()]);
+    let (op, dst, src) = decompose(opcode);
+    let rp = [Pair::BC, Pair::DE, Pair::HL, Pair::SP][dst as usize / 2];
+    let stack_rp = [StackPair::BC, StackPair::DE, StackPair::HL, StackPair::PSW][dst as usize / 2];
+    let indirect_rp = [IndirectPair::BC, IndirectPair::DE][dst as usize / 2 % 2];
+    let (to, from) = (Operand::from_bits(dst), Operand::from_bits(src));
+    let inst = match op {
+        0 => match (dst, src) {
+            (4, 0) => Rim,
+            (6, 0) => Sim,
+            (_, 0) => Nop,
+            (dst, 1) if dst % 2 == 0 => Lxi(rp, word),
+            (_, 1) => Dad(rp),
+            (0 | 2, 2) => Stax(indirect_rp),
+            (1 | 3, 2) => Ldax(indirect_rp),
+            (4, 2) => Shld(word),
+            (5, 2) => Lhld(word),
+            (6, 2) => Sta(word),
+            (7, 2) => Lda(word),
+            (dst, 3) if dst % 2 == 0 => Inx(rp),
+            (_, 3) => Dcx(rp),
+            (_, 4) => Inr(to),
+            (_, 5) => Dcr(to),
+            (_, 6) => Mvi(to, byte),
+            (dst, _) => [Rlc, Rrc, Ral, Rar, Daa, Cma, Stc, Cmc][dst as usize],
+        },
+        1 => match (dst, src) {
+            (6, 6) => Hlt,
+            _ => Mov(to, from),
+        },
+        2 => [Add, Adc, Sub, Sbb, Ana, Xra, Ora, Cmp][dst as usize](from),
+        _ => match (dst, src) {
+            (_, 0) => Ret(condition(dst)),
+            (dst, 1) if dst % 2 == 0 => Pop(stack_rp),
+            (1 | 3, 1) => Ret(Anytime),
+            (5, 1) => Pchl,
+            (_, 1) => Sphl,
+            (_, 2) => Jmp(condition(dst), word),
+            (0 | 1, 3) => Jmp(Anytime, word),
+            (2, 3) => Out(byte),
+            (3, 3) => In(byte),
+            (4, 3) => Xthl,
+            (5, 3) => Xchg,
+            (6, 3) => Di,
+            (_, 3) => Ei,
+            (_, 4) => Call(condition(dst), word),
+            (dst, 5) if dst % 2 == 0 => Push(stack_rp),
+            (_, 5) => Call(Anytime, word),
+            (_, 6) => [Adi, Aci, Sui, Sbi, Ani, Xri, Ori, Cpi][dst as usize](byte),
+            (n, _) => Rst(n),
+        },
+    };
+    Some((inst, inst.len()))
+}
+
+impl Instruction {
+    /// the length of the instruction in bytes, 1 to 3. never empty, so no `is_empty()`.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(self) -> usize {
+        use Instruction::*;
+        match self {
+            Lxi(..) | Shld(_) | Lhld(_) | Sta(_) | Lda(_) | Jmp(..) | Call(..) => 3,
+            Mvi(..) | Adi(_) | Aci(_) | Sui(_) | Sbi(_) | Ani(_) | Xri(_) | Ori(_) | Cpi(_)
+            | Out(_) | In(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_lxi() {
+        assert_eq!(
+            decode(&[0x21, 0x34, 0x12]),
+            Some((Instruction::Lxi(Pair::HL, 0x1234), 3))
+        );
+        assert_eq!(
+            decode(&[0x36, 0x2a]),
+            Some((Instruction::Mvi(Operand::M, 0x2a), 2))
+        );
+        assert_eq!(decode(&[]), None);
+    }
+}
@@ -1,13 +1,27 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
 pub mod bits;
 
 pub mod register;
 
 pub mod alu;
 
+pub mod decode;
+
+#[cfg(feature = "std")]
 pub mod memory;
 
+#[cfg(feature = "std")]
 pub mod processor;
 
+#[cfg(feature = "std")]
 pub mod bus;
 
+#[cfg(feature = "std")]
+pub mod clock;
+
+#[cfg(feature = "std")]
+pub mod peripheral;
+
+#[cfg(feature = "std")]
 pub mod instruction;
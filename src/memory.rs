@@ -1,55 +1,835 @@
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
 pub trait Memory: Send {
     type Data;
     type Address;
     fn write(&mut self, address: Self::Address, data: Self::Data);
     fn read(&self, address: Self::Address) -> Self::Data;
+    /// the data at `address` if it can be read without side effects, e.g. unlike a device
+    /// popping a FIFO on read. `None` unless the memory knows reading is harmless.
+    fn peek(&self, _address: Self::Address) -> Option<Self::Data> {
+        None
+    }
+    /// put back data read with `peek`, bypassing anything watching or guarding writes.
+    fn poke(&mut self, address: Self::Address, data: Self::Data) {
+        self.write(address, data)
+    }
+    /// the bytes in `range`, e.g. for a hex view.
+    fn dump(&self, range: Range<u16>) -> Vec<u8>
+    where
+        Self: Memory<Data = u8, Address = u16>,
+    {
+        range.map(|address| self.read(address)).collect()
+    }
+    /// `len` units from `start` on, wrapping past the top of the address space.
+    fn read_range(&self, start: Self::Address, len: usize) -> Vec<Self::Data>
+    where
+        Self: Memory<Data = u8, Address = u16>,
+    {
+        (0..len)
+            .map(|i| self.read(start.wrapping_add(i as u16)))
+            .collect()
+    }
+    /// write `data` from `start` on, wrapping past the top of the address space.
+    fn write_range(&mut self, start: Self::Address, data: &[Self::Data])
+    where
+        Self: Memory<Data = u8, Address = u16>,
+    {
+        for (i, &x) in data.iter().enumerate() {
+            self.write(start.wrapping_add(i as u16), x);
+        }
+    }
+}
+
+/// error on parsing a listing of `ADDR: BYTES` lines.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseError {
+    /// the line has no `:` between address and bytes.
+    MissingColon { line: usize },
+    /// the address isn't a 16-bit hex number.
+    InvalidAddress { line: usize },
+    /// a byte isn't an 8-bit hex number.
+    InvalidByte { line: usize },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::MissingColon { line } => write!(f, "line {line}: missing `:`"),
+            ParseError::InvalidAddress { line } => write!(f, "line {line}: invalid address"),
+            ParseError::InvalidByte { line } => write!(f, "line {line}: invalid byte"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// error on parsing an Intel HEX file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum HexError {
+    /// the record doesn't begin with `:`.
+    MissingStartCode { line: usize },
+    /// the record has a character other than hex digits, or an odd number of them.
+    InvalidDigit { line: usize },
+    /// the record is shorter or longer than its byte count says.
+    Truncated { line: usize },
+    /// the checksum byte doesn't match the record.
+    ChecksumMismatch {
+        line: usize,
+        expected: u8,
+        found: u8,
+    },
+    /// the record type is neither data (00) nor end of file (01).
+    UnsupportedRecordType { line: usize, record_type: u8 },
+    /// the data runs past the end of the address space.
+    AddressOutOfRange { line: usize },
+    /// no end of file record.
+    MissingEndOfFile,
+}
+
+impl std::fmt::Display for HexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HexError::MissingStartCode { line } => write!(f, "line {line}: missing `:`"),
+            HexError::InvalidDigit { line } => write!(f, "line {line}: invalid hex digits"),
+            HexError::Truncated { line } => write!(f, "line {line}: length mismatch"),
+            HexError::ChecksumMismatch {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line}: checksum {found:02X} where {expected:02X} expected"
+            ),
+            HexError::UnsupportedRecordType { line, record_type } => {
+                write!(f, "line {line}: unsupported record type {record_type:02X}")
+            }
+            HexError::AddressOutOfRange { line } => write!(f, "line {line}: address out of range"),
+            HexError::MissingEndOfFile => write!(f, "missing end of file record"),
+        }
+    }
 }
 
+impl std::error::Error for HexError {}
+
+struct WriteWatch(Box<dyn FnMut(u16, u8) + Send>);
+
+impl std::fmt::Debug for WriteWatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("WriteWatch")
+    }
+}
+
+/// `N` bytes of RAM, kept on the heap. addresses wrap around at `N`.
 #[derive(Debug)]
-pub struct RamB8A16 {
-    ram: [u8; u16::MAX as usize],
+pub struct Ram<const N: usize> {
+    ram: Box<[u8; N]>,
+    read_only: Vec<Range<u16>>,
+    protected_writes: u64,
+    write_watches: HashMap<u16, WriteWatch>,
 }
 
-impl Default for RamB8A16 {
+/// the whole 64K addressable by 16 bits, 0x0000-0xFFFF.
+pub type RamB8A16 = Ram<{ u16::MAX as usize + 1 }>;
+
+impl<const N: usize> Default for Ram<N> {
     fn default() -> Self {
         Self {
-            ram: [0; u16::MAX as usize],
+            ram: vec![0; N].into_boxed_slice().try_into().unwrap(),
+            read_only: vec![],
+            protected_writes: 0,
+            write_watches: HashMap::new(),
         }
     }
 }
 
-impl RamB8A16 {
+impl<const N: usize> Ram<N> {
+    pub const SIZE: usize = N;
+
     pub fn new() -> Self {
         Default::default()
     }
+    /// ignore writes into `range` from now on, like ROM. flashing still reaches it.
+    /// a write is checked at its address wrapped into RAM, so `range` should lie below `N`.
+    pub fn set_read_only(&mut self, range: Range<u16>) {
+        self.read_only.push(range);
+    }
+    /// how many writes were dropped for hitting a read-only range.
+    pub fn protected_write_count(&self) -> u64 {
+        self.protected_writes
+    }
+    /// call `f` with the address and data whenever a write lands on `addr`, replacing any
+    /// watch already there. flashing doesn't trigger it. as with read-only ranges, `addr`
+    /// is matched against the address wrapped into RAM.
+    pub fn set_write_watch(&mut self, addr: u16, f: Box<dyn FnMut(u16, u8) + Send>) {
+        self.write_watches.insert(addr, WriteWatch(f));
+    }
+    /// the bytes in `range`, wrapping at the end of RAM as `read` does.
+    pub fn dump(&self, range: Range<u16>) -> Vec<u8> {
+        self.read_range(range.start, range.len())
+    }
+    /// the pieces of `len` bytes from `start` that are contiguous in `ram`, as its index,
+    /// the offset into the `len` bytes and the length, split where RAM or addresses wrap.
+    fn spans(start: u16, len: usize) -> impl Iterator<Item = (usize, usize, usize)> {
+        let (mut address, mut done) = (start, 0);
+        std::iter::from_fn(move || {
+            if done == len {
+                return None;
+            }
+            let at = address as usize % N;
+            let n = (len - done)
+                .min(N - at)
+                .min(u16::MAX as usize + 1 - address as usize);
+            let span = (at, done, n);
+            done += n;
+            address = address.wrapping_add(n as u16);
+            Some(span)
+        })
+    }
+    /// copy `data` from `displacement` on past read-only ranges and write watches,
+    /// wrapping as `write_range` does.
     pub fn flash(&mut self, data: &[u8], displacement: u16) {
-        let displacement = displacement as usize;
-        for (i, &x) in data.iter().enumerate() {
-            let i = (displacement + i) % (u16::MAX as usize);
-            self.ram[i] = x;
+        for (at, offset, n) in Self::spans(displacement, data.len()) {
+            self.ram[at..at + n].copy_from_slice(&data[offset..offset + n]);
+        }
+    }
+    /// flash the bytes of `s` followed by `terminator` if any, e.g. `b'$'` for CP/M or 0 for C.
+    pub fn flash_str(&mut self, s: &str, at: u16, terminator: Option<u8>) {
+        self.flash(s.as_bytes(), at);
+        if let Some(terminator) = terminator {
+            self.flash(&[terminator], at.wrapping_add(s.len() as u16));
+        }
+    }
+    /// flash a listing of `0100: 3E 2A` lines, i.e. a hex address and hex bytes.
+    /// text after `;` is a comment. nothing is written unless the whole listing is valid.
+    pub fn flash_listing(&mut self, text: &str) -> Result<(), ParseError> {
+        let mut records = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (address, bytes) = line
+                .split_once(':')
+                .ok_or(ParseError::MissingColon { line: line_no })?;
+            let address = u16::from_str_radix(address.trim(), 16)
+                .map_err(|_| ParseError::InvalidAddress { line: line_no })?;
+            let bytes = bytes
+                .split_whitespace()
+                .map(|b| match b.len() {
+                    2 => u8::from_str_radix(b, 16).ok(),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(ParseError::InvalidByte { line: line_no })?;
+            records.push((address, bytes));
+        }
+        for (address, bytes) in records {
+            self.flash(&bytes, address);
+        }
+        Ok(())
+    }
+    /// flash data records of Intel HEX at their load addresses, up to the end of file record.
+    /// nothing is written unless the whole file is valid.
+    pub fn flash_intel_hex(&mut self, hex: &str) -> Result<(), HexError> {
+        let mut records = vec![];
+        for (i, line) in hex.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let digits = line
+                .strip_prefix(':')
+                .ok_or(HexError::MissingStartCode { line: line_no })?;
+            if digits.len() % 2 != 0 {
+                return Err(HexError::InvalidDigit { line: line_no });
+            }
+            let bytes = (0..digits.len())
+                .step_by(2)
+                .map(|i| {
+                    digits
+                        .get(i..i + 2)
+                        .and_then(|b| u8::from_str_radix(b, 16).ok())
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(HexError::InvalidDigit { line: line_no })?;
+            if bytes.len() < 5 || bytes.len() != 5 + bytes[0] as usize {
+                return Err(HexError::Truncated { line: line_no });
+            }
+            let (&found, body) = bytes.split_last().expect("checked to be 5 bytes or more");
+            let expected = body
+                .iter()
+                .fold(0u8, |acc, &x| acc.wrapping_add(x))
+                .wrapping_neg();
+            if found != expected {
+                return Err(HexError::ChecksumMismatch {
+                    line: line_no,
+                    expected,
+                    found,
+                });
+            }
+            let address = u16::from_be_bytes([body[1], body[2]]);
+            match body[3] {
+                0x00 => {
+                    let data = &body[4..];
+                    if address as usize + data.len() > self.ram.len() {
+                        return Err(HexError::AddressOutOfRange { line: line_no });
+                    }
+                    records.push((address, data.to_vec()));
+                }
+                0x01 => {
+                    for (address, data) in records {
+                        self.flash(&data, address);
+                    }
+                    return Ok(());
+                }
+                record_type => {
+                    return Err(HexError::UnsupportedRecordType {
+                        line: line_no,
+                        record_type,
+                    })
+                }
+            }
+        }
+        Err(HexError::MissingEndOfFile)
+    }
+    /// flash a gzip-compressed image, decompressing it on the fly.
+    /// returns the number of decompressed bytes written.
+    #[cfg(feature = "gzip")]
+    pub fn flash_gzip(&mut self, reader: impl std::io::Read, at: u16) -> std::io::Result<usize> {
+        use std::io::Read;
+        let mut decoder = flate2::read::GzDecoder::new(reader);
+        let mut buf = [0; 4096];
+        let mut written = 0;
+        loop {
+            let n = match decoder.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            self.flash(&buf[..n], at.wrapping_add(written as u16));
+            written += n;
+        }
+        Ok(written)
+    }
+}
+
+impl<const N: usize> Memory for Ram<N> {
+    type Data = u8;
+    type Address = u16;
+
+    fn write(&mut self, address: Self::Address, data: Self::Data) {
+        let at = (address as usize % N) as u16;
+        if self.read_only.iter().any(|range| range.contains(&at)) {
+            self.protected_writes += 1;
+            return;
+        }
+        self.ram[at as usize] = data;
+        if let Some(WriteWatch(f)) = self.write_watches.get_mut(&at) {
+            f(at, data)
+        }
+    }
+
+    fn read(&self, address: Self::Address) -> Self::Data {
+        self.ram[address as usize % N]
+    }
+
+    fn peek(&self, address: Self::Address) -> Option<Self::Data> {
+        Some(self.read(address))
+    }
+
+    /// ignores read-only ranges and write watches, as flashing does.
+    fn poke(&mut self, address: Self::Address, data: Self::Data) {
+        self.ram[address as usize % N] = data;
+    }
+
+    fn dump(&self, range: Range<u16>) -> Vec<u8> {
+        Ram::dump(self, range)
+    }
+
+    fn read_range(&self, start: Self::Address, len: usize) -> Vec<Self::Data> {
+        let mut data = vec![0; len];
+        for (at, offset, n) in Self::spans(start, len) {
+            data[offset..offset + n].copy_from_slice(&self.ram[at..at + n]);
+        }
+        data
+    }
+
+    /// copies slices unless read-only ranges or write watches need each byte checked.
+    fn write_range(&mut self, start: Self::Address, data: &[Self::Data]) {
+        if !self.read_only.is_empty() || !self.write_watches.is_empty() {
+            for (i, &x) in data.iter().enumerate() {
+                self.write(start.wrapping_add(i as u16), x);
+            }
+            return;
+        }
+        for (at, offset, n) in Self::spans(start, data.len()) {
+            self.ram[at..at + n].copy_from_slice(&data[offset..offset + n]);
+        }
+    }
+}
+
+/// a device mapped into memory, addressed from the start of its range.
+pub trait MemoryRegion: Send {
+    fn read(&self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, data: u8);
+}
+
+/// a FIFO the host pushes bytes into and the guest drains, e.g. a serial receiver.
+/// reading offset 0 pops the next byte, or 0 if empty. reading offset 1 gives the status,
+/// [`RingBufferDevice::READY`] if not empty and [`RingBufferDevice::FULL`] if full.
+/// clones share the buffer, so the host can keep one after mapping another.
+#[derive(Debug, Clone)]
+pub struct RingBufferDevice {
+    buffer: Arc<Mutex<VecDeque<u8>>>,
+    capacity: usize,
+}
+
+impl RingBufferDevice {
+    pub const DATA: u16 = 0;
+    pub const STATUS: u16 = 1;
+    pub const READY: u8 = 0x01;
+    pub const FULL: u8 = 0x02;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+    /// the byte is dropped if the buffer is full, as a receiver overruns.
+    pub fn push(&mut self, byte: u8) {
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.len() < self.capacity {
+            buffer.push_back(byte);
+        }
+    }
+}
+
+impl MemoryRegion for RingBufferDevice {
+    fn read(&self, offset: u16) -> u8 {
+        let mut buffer = self.buffer.lock().unwrap();
+        match offset {
+            Self::DATA => buffer.pop_front().unwrap_or_default(),
+            Self::STATUS => {
+                let mut status = 0;
+                if !buffer.is_empty() {
+                    status |= Self::READY;
+                }
+                if buffer.len() >= self.capacity {
+                    status |= Self::FULL;
+                }
+                status
+            }
+            _ => 0,
+        }
+    }
+
+    /// writes from the guest are ignored.
+    fn write(&mut self, _offset: u16, _data: u8) {}
+}
+
+/// RAM with devices mapped over ranges of it. an access goes to the first region
+/// containing its address, or to the RAM if none does.
+#[derive(Default)]
+pub struct MappedMemory {
+    ram: RamB8A16,
+    regions: Vec<(Range<u16>, Box<dyn MemoryRegion>)>,
+}
+
+impl MappedMemory {
+    pub fn new(ram: RamB8A16) -> Self {
+        Self {
+            ram,
+            regions: vec![],
+        }
+    }
+    pub fn map(&mut self, range: Range<u16>, region: Box<dyn MemoryRegion>) {
+        self.regions.push((range, region));
+    }
+    pub fn ram_mut(&mut self) -> &mut RamB8A16 {
+        &mut self.ram
+    }
+}
+
+impl std::fmt::Debug for MappedMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedMemory")
+            .field("ram", &self.ram)
+            .field(
+                "regions",
+                &self.regions.iter().map(|(r, _)| r).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Memory for MappedMemory {
+    type Data = u8;
+    type Address = u16;
+
+    fn write(&mut self, address: Self::Address, data: Self::Data) {
+        match self.regions.iter_mut().find(|(r, _)| r.contains(&address)) {
+            Some((range, region)) => region.write(address - range.start, data),
+            None => self.ram.write(address, data),
+        }
+    }
+
+    fn read(&self, address: Self::Address) -> Self::Data {
+        match self.regions.iter().find(|(r, _)| r.contains(&address)) {
+            Some((range, region)) => region.read(address - range.start),
+            None => self.ram.read(address),
+        }
+    }
+
+    /// `None` over a mapped region, since reading a device may change it.
+    fn peek(&self, address: Self::Address) -> Option<Self::Data> {
+        match self.regions.iter().any(|(r, _)| r.contains(&address)) {
+            true => None,
+            false => self.ram.peek(address),
         }
     }
+
+    fn poke(&mut self, address: Self::Address, data: Self::Data) {
+        match self.regions.iter_mut().find(|(r, _)| r.contains(&address)) {
+            Some((range, region)) => region.write(address - range.start, data),
+            None => self.ram.poke(address, data),
+        }
+    }
+}
+
+/// 16K banks paged into the four 16K slots of the address space, slot `n` covering
+/// `n * 0x4000` on. slot `n` starts out with bank `n` modulo the number of banks.
+#[derive(Debug)]
+pub struct BankedMemory {
+    banks: Vec<Box<[u8; BankedMemory::BANK_SIZE]>>,
+    slots: [usize; 4],
 }
 
-impl Memory for RamB8A16 {
+impl BankedMemory {
+    pub const BANK_SIZE: usize = 0x4000;
+
+    pub fn new(banks: usize) -> Self {
+        assert!(banks > 0, "no banks to map");
+        Self {
+            banks: (0..banks).map(|_| Box::new([0; Self::BANK_SIZE])).collect(),
+            slots: std::array::from_fn(|slot| slot % banks),
+        }
+    }
+    /// map `bank` into `slot`. panics if either is out of range.
+    pub fn select_bank(&mut self, slot: usize, bank: usize) {
+        assert!(bank < self.banks.len(), "no bank {bank}");
+        self.slots[slot] = bank;
+    }
+    /// the bank mapped into `slot`.
+    pub fn selected_bank(&self, slot: usize) -> usize {
+        self.slots[slot]
+    }
+    fn locate(&self, address: u16) -> (usize, usize) {
+        let address = address as usize;
+        (
+            self.slots[address / Self::BANK_SIZE],
+            address % Self::BANK_SIZE,
+        )
+    }
+}
+
+impl Memory for BankedMemory {
     type Data = u8;
     type Address = u16;
 
     fn write(&mut self, address: Self::Address, data: Self::Data) {
-        self.ram[address as usize] = data
+        let (bank, offset) = self.locate(address);
+        self.banks[bank][offset] = data;
     }
 
     fn read(&self, address: Self::Address) -> Self::Data {
-        self.ram[address as usize]
+        let (bank, offset) = self.locate(address);
+        self.banks[bank][offset]
+    }
+
+    fn peek(&self, address: Self::Address) -> Option<Self::Data> {
+        Some(self.read(address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn banked_memory() {
+        let mut memory = BankedMemory::new(6);
+        assert_eq!(memory.selected_bank(3), 3);
+        memory.select_bank(2, 4);
+        memory.write(0x8010, 0x44);
+        memory.select_bank(2, 5);
+        assert_eq!(memory.read(0x8010), 0x00);
+        memory.write(0x8010, 0x55);
+        memory.select_bank(2, 4);
+        assert_eq!(memory.read(0x8010), 0x44);
+        memory.select_bank(0, 5);
+        assert_eq!(memory.read(0x0010), 0x55);
+        memory.write(0xffff, 0x33);
+        memory.select_bank(1, 3);
+        assert_eq!(memory.read(0x7fff), 0x33);
+
+        let mut single = BankedMemory::new(1);
+        single.write(0x0001, 0x11);
+        assert_eq!(single.dump(0xc000..0xc002), [0x00, 0x11]);
+    }
+
+    #[test]
+    fn mapped_memory() {
+        #[derive(Default)]
+        struct Device {
+            written: Arc<Mutex<Vec<(u16, u8)>>>,
+        }
+        impl MemoryRegion for Device {
+            fn read(&self, offset: u16) -> u8 {
+                0x40 + offset as u8
+            }
+            fn write(&mut self, offset: u16, data: u8) {
+                self.written.lock().unwrap().push((offset, data))
+            }
+        }
+        let device = Device::default();
+        let written = Arc::clone(&device.written);
+        let mut memory = MappedMemory::new(RamB8A16::new());
+        memory.map(0x2000..0x2010, Box::new(device));
+        memory.write(0x2003, 0xaa);
+        memory.write(0x2010, 0xbb);
+        memory.write(0x1fff, 0xcc);
+        assert_eq!(*written.lock().unwrap(), [(0x0003, 0xaa)]);
+        assert_eq!(memory.read(0x2003), 0x43);
+        assert_eq!(memory.read(0x200f), 0x4f);
+        assert_eq!([memory.read(0x2010), memory.read(0x1fff)], [0xbb, 0xcc]);
+        assert_eq!(memory.ram_mut().read(0x2003), 0x00);
+    }
+
+    #[test]
+    fn ring_buffer_status() {
+        use RingBufferDevice as Ring;
+        let mut ring = Ring::new(2);
+        let device = ring.clone();
+        assert_eq!(device.read(Ring::STATUS), 0);
+        ring.push(1);
+        assert_eq!(device.read(Ring::STATUS), Ring::READY);
+        ring.push(2);
+        ring.push(3);
+        assert_eq!(device.read(Ring::STATUS), Ring::READY | Ring::FULL);
+        assert_eq!(device.read(Ring::DATA), 1);
+        assert_eq!(device.read(Ring::STATUS), Ring::READY);
+        assert_eq!(device.read(Ring::DATA), 2);
+        assert_eq!(device.read(Ring::STATUS), 0);
+        assert_eq!(device.read(Ring::DATA), 0);
+    }
+
+    #[test]
+    fn full_address_space() {
+        let mut ram = RamB8A16::new();
+        ram.write(0xffff, 0x5a);
+        assert_eq!(ram.read(0xffff), 0x5a);
+        ram.flash(&[0x01, 0x02, 0x03], 0xfffe);
+        assert_eq!([ram.read(0xfffe), ram.read(0xffff)], [0x01, 0x02]);
+        assert_eq!(ram.read(0x0000), 0x03);
+    }
+
+    #[test]
+    fn small_ram() {
+        let mut ram = Ram::<256>::new();
+        ram.write(0x00ff, 0x11);
+        ram.write(0x0100, 0x22);
+        assert_eq!([ram.read(0x00ff), ram.read(0x0000)], [0x11, 0x22]);
+        assert_eq!(ram.read(0x12ff), 0x11);
+        ram.flash(&[0x33, 0x44], 0x00ff);
+        assert_eq!([ram.read(0x00ff), ram.read(0x0000)], [0x33, 0x44]);
+        assert_eq!(ram.dump(0x00fe..0x0101), [0x00, 0x33, 0x44]);
+        assert_eq!(ram.dump(0x01fe..0x0200), ram.dump(0x00fe..0x0100));
+    }
+
+    #[test]
+    fn small_ram_wrapped_writes() {
+        let mut ram = Ram::<256>::new();
+        ram.set_read_only(0x0000..0x0010);
+        ram.write(0x0100, 0x11);
+        ram.write(0x120f, 0x22);
+        assert_eq!(ram.dump(0x0000..0x0010), [0; 0x10]);
+        assert_eq!(ram.protected_write_count(), 2);
+
+        let writes = Arc::new(Mutex::new(vec![]));
+        let seen = writes.clone();
+        ram.set_write_watch(
+            0x0020,
+            Box::new(move |addr, data| seen.lock().unwrap().push((addr, data))),
+        );
+        ram.write(0x0120, 0x33);
+        ram.write(0xff20, 0x44);
+        assert_eq!(*writes.lock().unwrap(), [(0x0020, 0x33), (0x0020, 0x44)]);
+    }
+
+    #[test]
+    fn ranges() {
+        let block = (1..=8).collect::<Vec<u8>>();
+        let mut ram = RamB8A16::new();
+        ram.write_range(0xfffc, &block);
+        assert_eq!(ram.dump(0xfffc..0xffff), [1, 2, 3]);
+        assert_eq!(ram.read(0xffff), 4);
+        assert_eq!(ram.dump(0x0000..0x0004), [5, 6, 7, 8]);
+        assert_eq!(ram.read_range(0xfffc, 8), block);
+
+        // wraps at the end of RAM and at 0xFFFF alike
+        let mut small = Ram::<3>::new();
+        small.write_range(0xfffe, &[1, 2, 3, 4, 5]);
+        assert_eq!(small.dump(0..3), [3, 4, 5]);
+        assert_eq!(small.read_range(0xffff, 4), [3, 3, 4, 5]);
+        let mut flashed = Ram::<3>::new();
+        flashed.flash(&[1, 2, 3, 4, 5], 0xfffe);
+        assert_eq!(flashed.dump(0..3), small.dump(0..3));
+
+        // the trait defaults give the same
+        let mut mapped = MappedMemory::new(RamB8A16::new());
+        mapped.write_range(0xfffc, &block);
+        assert_eq!(mapped.read_range(0xfffc, 8), block);
+        assert_eq!(mapped.ram_mut().dump(0x0000..0x0004), [5, 6, 7, 8]);
+
+        // read-only ranges still hold
+        ram.set_read_only(0x0000..0x0002);
+        ram.write_range(0xffff, &[0; 4]);
+        assert_eq!(ram.read_range(0xffff, 4), [0, 5, 6, 0]);
+    }
+
+    #[test]
+    fn flash_str() {
+        let mut ram = RamB8A16::new();
+        ram.flash_str("HELLO", 0x0200, Some(b'$'));
+        let flashed = (0x0200..0x0207).map(|a| ram.read(a)).collect::<Vec<_>>();
+        assert_eq!(flashed, b"HELLO$\0");
+        ram.flash_str("HI", 0x0200, None);
+        let flashed = (0x0200..0x0207).map(|a| ram.read(a)).collect::<Vec<_>>();
+        assert_eq!(flashed, b"HILLO$\0");
+    }
+
+    #[test]
+    fn flash_listing() {
+        let mut ram = RamB8A16::new();
+        let listing = "0100: 3E 2A ; MVI A,42\n\n0102: 32 00 02\n";
+        ram.flash_listing(listing).unwrap();
+        let flashed = (0x0100..0x0105).map(|a| ram.read(a)).collect::<Vec<_>>();
+        assert_eq!(flashed, [0x3e, 0x2a, 0x32, 0x00, 0x02]);
+        assert_eq!(
+            ram.flash_listing("0200: 01\n02G0: 02"),
+            Err(ParseError::InvalidAddress { line: 2 })
+        );
+        assert_eq!(ram.read(0x0200), 0x00);
+        assert_eq!(
+            ram.flash_listing("0200 01"),
+            Err(ParseError::MissingColon { line: 1 })
+        );
+        assert_eq!(
+            ram.flash_listing("0200: 01 2"),
+            Err(ParseError::InvalidByte { line: 1 })
+        );
+    }
+
+    #[test]
+    fn flash_intel_hex() {
+        let mut ram = RamB8A16::new();
+        let hex = ":030100003E2A3262\n:0201030000FA00\n\n:00000001FF\n";
+        ram.flash_intel_hex(hex).unwrap();
+        let flashed = (0x0100..0x0105).map(|a| ram.read(a)).collect::<Vec<_>>();
+        assert_eq!(flashed, [0x3e, 0x2a, 0x32, 0x00, 0xfa]);
+    }
+
+    #[test]
+    fn flash_intel_hex_errors() {
+        let mut ram = RamB8A16::new();
+        assert_eq!(
+            ram.flash_intel_hex(":0202000001025A\n:00000001FF"),
+            Err(HexError::ChecksumMismatch {
+                line: 1,
+                expected: 0xf9,
+                found: 0x5a
+            })
+        );
+        assert_eq!(
+            ram.flash_intel_hex(":020200000102F9\n:0302000001\n:00000001FF"),
+            Err(HexError::Truncated { line: 2 })
+        );
+        assert_eq!(ram.read(0x0200), 0x00);
+        assert_eq!(
+            ram.flash_intel_hex("020200000102F9"),
+            Err(HexError::MissingStartCode { line: 1 })
+        );
+        assert_eq!(
+            ram.flash_intel_hex(":0202000001G2F9"),
+            Err(HexError::InvalidDigit { line: 1 })
+        );
+        assert_eq!(
+            ram.flash_intel_hex(":02FFFF000102FD\n:00000001FF"),
+            Err(HexError::AddressOutOfRange { line: 1 })
+        );
+        assert_eq!(
+            ram.flash_intel_hex(":020000040000FA\n:00000001FF"),
+            Err(HexError::UnsupportedRecordType {
+                line: 1,
+                record_type: 4
+            })
+        );
+        assert_eq!(
+            ram.flash_intel_hex(":020200000102F9"),
+            Err(HexError::MissingEndOfFile)
+        );
+    }
+
+    #[test]
+    fn dump() {
+        let mut ram = RamB8A16::new();
+        ram.flash(&[0xde, 0xad, 0xbe, 0xef, 0x01], 0x10);
+        assert_eq!(ram.dump(0x10..0x14), [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(Memory::dump(&ram, 0x10..0x14), [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(ram.dump(0xfff0..0xffff).len(), 0x0f);
+        let (start, end) = (0x14, 0x10);
+        assert!(ram.dump(start..end).is_empty());
+
+        let mapped = MappedMemory::new(ram);
+        assert_eq!(mapped.dump(0x11..0x13), [0xad, 0xbe]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn flash_gzip() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+        let data = (0..=255u8).cycle().take(10000).collect::<Vec<_>>();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut ram = RamB8A16::new();
+        assert_eq!(ram.flash_gzip(compressed.as_slice(), 0x100).unwrap(), 10000);
+        for (i, &x) in data.iter().enumerate() {
+            assert_eq!(ram.read(0x100 + i as u16), x);
+        }
+        assert!(ram.flash_gzip(&[1, 2, 3][..], 0).is_err());
     }
 }
 
-mod gb {
-    struct GBMemory {
+/// the memory map of the Game Boy's LR35902, without a memory bank controller.
+pub mod gb {
+    use super::Memory;
+
+    #[derive(Debug)]
+    pub struct GBMemory {
         /// 0x0000-0x3FFF
-        cartridge_rom_00: [u8; 32768],
+        cartridge_rom_00: [u8; 0x4000],
         /// 0x4000-0x7FFF
-        cartridge_rom_01: [u8; 32768],
+        cartridge_rom_01: [u8; 0x4000],
         /// 0x8000-0x9FFF
         video_ram: [u8; 8192],
         /// 0xA000-0xBFFF
@@ -59,7 +839,7 @@ mod gb {
         /// 0xD000-0xDFFF
         work_ram_1: [u8; 4096],
         /// 0xFE00-0xFE9F
-        sprite_attribute_table: [u8; 256],
+        sprite_attribute_table: [u8; 160],
         /// 0xFF00-0xFF7F
         io_ports: [u8; 128],
         /// 0XFF80-0xFFFE
@@ -67,4 +847,119 @@ mod gb {
         /// 0xFFFF
         interrupt_enable_register: u8,
     }
+
+    impl Default for GBMemory {
+        fn default() -> Self {
+            Self {
+                cartridge_rom_00: [0; 0x4000],
+                cartridge_rom_01: [0; 0x4000],
+                video_ram: [0; 8192],
+                cartridge_ram: [0; 8192],
+                work_ram_0: [0; 4096],
+                work_ram_1: [0; 4096],
+                sprite_attribute_table: [0; 160],
+                io_ports: [0; 128],
+                high_rom: [0; 127],
+                interrupt_enable_register: 0,
+            }
+        }
+    }
+
+    impl GBMemory {
+        pub fn new() -> Self {
+            Default::default()
+        }
+        /// load the first two banks of a cartridge, which writes can't reach.
+        pub fn load_rom(&mut self, rom: &[u8]) {
+            let (bank_00, bank_01) = rom.split_at(rom.len().min(0x4000));
+            self.cartridge_rom_00[..bank_00.len()].copy_from_slice(bank_00);
+            let bank_01 = &bank_01[..bank_01.len().min(0x4000)];
+            self.cartridge_rom_01[..bank_01.len()].copy_from_slice(bank_01);
+        }
+        /// the byte a write to `address` lands on, `None` for ROM and the unusable
+        /// 0xFEA0-0xFEFF. echo RAM at 0xE000-0xFDFF mirrors 0xC000-0xDDFF.
+        fn region_mut(&mut self, address: u16) -> Option<&mut u8> {
+            let address = address as usize;
+            match address {
+                0x0000..=0x7fff | 0xfea0..=0xfeff => None,
+                0x8000..=0x9fff => Some(&mut self.video_ram[address - 0x8000]),
+                0xa000..=0xbfff => Some(&mut self.cartridge_ram[address - 0xa000]),
+                0xc000..=0xcfff => Some(&mut self.work_ram_0[address - 0xc000]),
+                0xd000..=0xdfff => Some(&mut self.work_ram_1[address - 0xd000]),
+                0xe000..=0xfdff => self.region_mut((address - 0x2000) as u16),
+                0xfe00..=0xfe9f => Some(&mut self.sprite_attribute_table[address - 0xfe00]),
+                0xff00..=0xff7f => Some(&mut self.io_ports[address - 0xff00]),
+                0xff80..=0xfffe => Some(&mut self.high_rom[address - 0xff80]),
+                _ => Some(&mut self.interrupt_enable_register),
+            }
+        }
+    }
+
+    impl Memory for GBMemory {
+        type Data = u8;
+        type Address = u16;
+
+        /// writes to ROM and the unusable area are dropped.
+        fn write(&mut self, address: Self::Address, data: Self::Data) {
+            if let Some(byte) = self.region_mut(address) {
+                *byte = data
+            }
+        }
+
+        /// the unusable area reads 0.
+        fn read(&self, address: Self::Address) -> Self::Data {
+            let address = address as usize;
+            match address {
+                0x0000..=0x3fff => self.cartridge_rom_00[address],
+                0x4000..=0x7fff => self.cartridge_rom_01[address - 0x4000],
+                0x8000..=0x9fff => self.video_ram[address - 0x8000],
+                0xa000..=0xbfff => self.cartridge_ram[address - 0xa000],
+                0xc000..=0xcfff => self.work_ram_0[address - 0xc000],
+                0xd000..=0xdfff => self.work_ram_1[address - 0xd000],
+                0xe000..=0xfdff => self.read((address - 0x2000) as u16),
+                0xfe00..=0xfe9f => self.sprite_attribute_table[address - 0xfe00],
+                0xfea0..=0xfeff => 0,
+                0xff00..=0xff7f => self.io_ports[address - 0xff00],
+                0xff80..=0xfffe => self.high_rom[address - 0xff80],
+                _ => self.interrupt_enable_register,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn regions() {
+            let mut memory = GBMemory::new();
+            let writable = [
+                0x8000, 0x9fff, 0xa000, 0xbfff, 0xc000, 0xcfff, 0xd000, 0xdfff, 0xfe00, 0xfe9f,
+                0xff00, 0xff7f, 0xff80, 0xfffe, 0xffff,
+            ];
+            for (i, &address) in writable.iter().enumerate() {
+                memory.write(address, i as u8 + 1);
+            }
+            for (i, &address) in writable.iter().enumerate() {
+                assert_eq!(memory.read(address), i as u8 + 1, "{address:#06x}");
+            }
+            let mut untouched =
+                (0..=0xffff).filter(|a| !writable.contains(a) && !(0xe000..0xfe00).contains(a));
+            assert!(untouched.all(|a| memory.read(a) == 0));
+
+            // echo RAM
+            memory.write(0xe123, 0x42);
+            assert_eq!(memory.read(0xc123), 0x42);
+            memory.write(0xd456, 0x24);
+            assert_eq!(memory.read(0xf456), 0x24);
+
+            // ROM and the unusable area ignore writes
+            memory.load_rom(&[0x31; 0x8000]);
+            memory.write(0x0100, 0x00);
+            memory.write(0x4100, 0x00);
+            memory.write(0xfea0, 0xff);
+            assert_eq!([memory.read(0x0100), memory.read(0x4100)], [0x31, 0x31]);
+            assert_eq!(memory.read(0xfea0), 0);
+        }
+    }
 }
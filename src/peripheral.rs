@@ -0,0 +1,125 @@
+use std::fmt::Debug;
+use std::sync::mpsc::Receiver;
+
+/// a device advancing along with an emulated processor, e.g. a timer or a shift register.
+pub trait Peripheral: Debug {
+    /// called after each instruction or interrupt with the T-states it took.
+    fn tick(&mut self, cycles: u64);
+
+    /// called on every `OUT port`.
+    fn on_out(&mut self, _port: u8, _value: u8) {}
+
+    /// called on every `IN port`. `None` leaves the port to the others.
+    fn on_in(&mut self, _port: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// where `IN port` reads from, without having to answer at once.
+pub trait InputSource: Debug {
+    /// the byte for `IN port`, or `None` if there is none yet, which makes the `IN` retry.
+    fn poll(&mut self, port: u8) -> Option<u8>;
+}
+
+/// waits for each byte to arrive on the channel. a closed channel reads 0xFF.
+#[derive(Debug)]
+pub struct BlockingInput(pub Receiver<u8>);
+
+impl InputSource for BlockingInput {
+    fn poll(&mut self, _port: u8) -> Option<u8> {
+        Some(self.0.recv().unwrap_or(0xff))
+    }
+}
+
+/// takes a byte from the channel if one has arrived, for hosts running an event loop.
+#[derive(Debug)]
+pub struct NonBlockingInput(pub Receiver<u8>);
+
+impl InputSource for NonBlockingInput {
+    fn poll(&mut self, _port: u8) -> Option<u8> {
+        self.0.try_recv().ok()
+    }
+}
+
+/// the shift hardware of Taito 8080 boards such as Space Invaders. `OUT 4` shifts a byte
+/// into the top of a 16-bit register, `OUT 2` sets the offset, and `IN 3` reads the 8 bits
+/// starting `offset` bits below the top.
+#[derive(Debug, Default, Clone)]
+pub struct ShiftRegister {
+    value: u16,
+    offset: u8,
+}
+
+impl ShiftRegister {
+    pub const OFFSET_PORT: u8 = 2;
+    pub const RESULT_PORT: u8 = 3;
+    pub const DATA_PORT: u8 = 4;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Peripheral for ShiftRegister {
+    fn tick(&mut self, _cycles: u64) {}
+
+    fn on_out(&mut self, port: u8, value: u8) {
+        match port {
+            Self::OFFSET_PORT => self.offset = value & 0x7,
+            Self::DATA_PORT => self.value = (value as u16) << 8 | self.value >> 8,
+            _ => {}
+        }
+    }
+
+    fn on_in(&mut self, port: u8) -> Option<u8> {
+        (port == Self::RESULT_PORT).then(|| (self.value >> (8 - self.offset)) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::i8080::{I8080Console, I8080RegisterCode16};
+
+    #[test]
+    fn input_sources() {
+        use std::sync::mpsc::channel;
+        let (tx, rx) = channel();
+        let mut source = NonBlockingInput(rx);
+        assert_eq!(source.poll(0), None);
+        tx.send(0x42).unwrap();
+        assert_eq!(source.poll(0), Some(0x42));
+
+        let (tx, rx) = channel();
+        let mut source = BlockingInput(rx);
+        std::thread::spawn(move || tx.send(0x24).unwrap());
+        assert_eq!(source.poll(0), Some(0x24));
+        assert_eq!(source.poll(0), Some(0xff));
+    }
+
+    #[test]
+    fn shift_register() {
+        // MVI A,FF; OUT 4; MVI A,0; OUT 4; MVI A,2; OUT 2; IN 3; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[
+            0x3e, 0xff, 0xd3, 0x04, 0x3e, 0x00, 0xd3, 0x04, 0x3e, 0x02, 0xd3, 0x02, 0xdb, 0x03,
+            0x76,
+        ]);
+        c.add_peripheral(Box::new(ShiftRegister::new()));
+        c.run();
+        assert_eq!(c.reg16(I8080RegisterCode16::PSW) >> 8, 0x03);
+    }
+
+    #[test]
+    fn shift_register_offsets() {
+        let mut shift = ShiftRegister::new();
+        shift.on_out(4, 0xab);
+        shift.on_out(4, 0xcd);
+        assert_eq!(shift.on_in(3), Some(0xcd));
+        shift.on_out(2, 4);
+        assert_eq!(shift.on_in(3), Some(0xda));
+        shift.on_out(2, 7);
+        assert_eq!(shift.on_in(3), Some(0xd5));
+        assert_eq!(shift.on_in(1), None);
+    }
+}
@@ -1,19 +1,29 @@
 use crate::alu::bit8::{Adder, DecimalAdjuster, IncDecOperator, LogicalOperator, Rotator};
 use crate::alu::{StatusFlag, ALU};
-use crate::bus::{AddressBus, DataBus, DataBusLoad, DataBusRead};
+use crate::bus::{AddressBus, DataBus, DataBusLoad, DataBusRead, Signal};
+use crate::clock::Clock;
+use crate::decode::decompose;
+pub use crate::decode::I8080JumpCondition;
 use crate::memory::{Memory, RamB8A16};
+use crate::peripheral::{InputSource, Peripheral};
 use crate::processor::{
     AddressingRegisterCode, DataRegisterCode, ProcAddressingRegisters, ProcDataRegisters,
     ProcMemory,
 };
-use crate::register::bit8::{Register8, Register8Pair};
+use crate::register::bit8::Register8;
 use crate::register::Register;
 use enumset::EnumSet;
 use std::cell::Cell;
-use std::collections::HashMap;
-use std::io::Read;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use timing::I8080Timing;
+
+pub mod asm;
+pub mod decode;
+pub mod disasm;
+pub mod timing;
 
 #[derive(Debug, Clone)]
 pub struct I8080DataReg {
@@ -21,6 +31,24 @@ pub struct I8080DataReg {
     bus: Rc<Cell<u8>>,
 }
 
+/// registers indexed by [`I8080RegisterCode::index`], all connected to the data bus.
+#[derive(Debug)]
+struct RegisterFile {
+    bus: Rc<Cell<u8>>,
+    regs: [I8080DataReg; I8080RegisterCode::COUNT],
+}
+
+impl Default for RegisterFile {
+    fn default() -> Self {
+        let bus = Rc::default();
+        let regs = std::array::from_fn(|_| I8080DataReg {
+            reg: Default::default(),
+            bus: Rc::clone(&bus),
+        });
+        Self { bus, regs }
+    }
+}
+
 impl DataBusRead for I8080DataReg {
     type DataBus = u8;
 
@@ -37,40 +65,321 @@ impl DataBusLoad for I8080DataReg {
     }
 }
 
+impl crate::bus::Bus<u8> for u8 {
+    fn set(&mut self, value: u8) {
+        *self = value
+    }
+
+    fn get(&self) -> u8 {
+        *self
+    }
+}
+
 impl DataBus for u8 {
     type Data = u8;
+}
 
-    fn set_data(&mut self, data: Self::Data) {
-        *self = data
+impl crate::bus::Bus<u16> for u16 {
+    fn set(&mut self, value: u16) {
+        *self = value
     }
 
-    fn get_data(&self) -> Self::Data {
+    fn get(&self) -> u16 {
         *self
     }
 }
 
 impl AddressBus for u16 {
     type Address = u16;
+}
 
-    fn set_address(&mut self, data: Self::Address) {
-        *self = data
+/// an 8080 whose bus cycles go to the memory `M`, flat 64K RAM by default.
+#[derive(Default, Debug)]
+pub struct I8080Console<M = RamB8A16> {
+    address_bus: Rc<Cell<u16>>,
+    memory: Arc<Mutex<M>>,
+    regs: RegisterFile,
+    /// HLTA
+    halted: Signal,
+    cycle_type: Option<I8080CycleType>,
+    /// the latest bus cycle, for `step_cycle()` to report.
+    last_cycle: Option<CycleInfo>,
+    /// the instruction `step_cycle()` left partway through its machine cycles.
+    in_flight: Option<InFlight<M>>,
+    timing: I8080Timing,
+    branch_taken: bool,
+    cycles: u64,
+    instructions_executed: u64,
+    io_space: IoSpace,
+    io_handlers: IoHandlers,
+    variant: I8080Variant,
+    /// INTE
+    interrupt_enabled: Signal,
+    /// WR, asserted while the last bus cycle writes.
+    write: Signal,
+    /// DBIN, asserted while the last bus cycle reads.
+    data_in: Signal,
+    /// RST 5.5, 6.5 and 7.5 of 8085 at bit 0, 1 and 2.
+    rst_pending: u8,
+    rst_unmasked: u8,
+    stop_request: Arc<AtomicBool>,
+    clear_scratch: bool,
+    stuck_watchdog: Option<u32>,
+    /// bus cycles that wrote to memory or a port, for the watchdog to tell progress by.
+    write_cycles: u64,
+    reset_vector: u16,
+    breakpoints: HashSet<u16>,
+    clock: Option<Box<dyn Clock>>,
+    peripherals: Vec<Box<dyn Peripheral>>,
+    input_sources: HashMap<u8, Box<dyn InputSource>>,
+    /// the port the last instruction waited on, having found no input there.
+    input_pending: Option<u8>,
+    trace: Option<Trace>,
+    history: Option<History>,
+    /// bytes `fetch_instruction()` takes in place of memory, set by `execute_opcode()`.
+    staged: VecDeque<u8>,
+    /// executions by opcode, while profiling.
+    opcode_histogram: Option<[u64; 256]>,
+    /// steps by opcode, built on the first `execute()`.
+    dispatch: Option<Dispatch<M>>,
+    /// serve CP/M BDOS calls at 0x0005 and warm boot at 0x0000, set by `load_com()`.
+    bdos: bool,
+    #[cfg(feature = "z80")]
+    shadow: ShadowRegisters,
+}
+
+/// the shadow set A' F' B' C' D' E' H' L' of the Z80, swapped in by `EX AF,AF'` and `EXX`.
+/// the 8080 instructions never touch it.
+#[cfg(feature = "z80")]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ShadowRegisters {
+    pub a: u8,
+    pub flag: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+}
+
+/// an instruction about to be executed, passed to the hook of [`I8080Console::set_trace`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TraceEvent {
+    /// address the opcode was fetched from.
+    pub pc: u16,
+    pub opcode: u8,
+    /// the state before the fetch.
+    pub state: I8080State,
+}
+
+struct Trace(Box<dyn FnMut(TraceEvent)>);
+
+impl std::fmt::Debug for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Trace")
     }
+}
+
+/// the output port CP/M console output of [`I8080Console::load_com`] goes to.
+pub const CPM_CONSOLE_PORT: u8 = 0x00;
 
-    fn get_address(&self) -> Self::Address {
+/// a step of an instruction given the `dst` and `src` fields of its opcode. the first step
+/// is the work done within the opcode fetch, and each other one runs a single further
+/// machine cycle with the work around it.
+type Step<M> = fn(&mut I8080Console<M>, u8, u8);
+
+/// the steps of an instruction. none takes more than five machine cycles.
+struct Program<M> {
+    steps: [Step<M>; 5],
+    len: u8,
+    /// the step checking the condition, after which the rest runs only if it holds.
+    condition: Option<u8>,
+}
+
+impl<M> Clone for Program<M> {
+    fn clone(&self) -> Self {
         *self
     }
 }
 
-#[derive(Default, Debug)]
-pub struct I8080Console<M> {
-    data_bus: Rc<Cell<u8>>,
-    address_bus: Rc<Cell<u16>>,
-    memory: Arc<Mutex<M>>,
-    regs: HashMap<I8080RegisterCode, I8080DataReg>,
-    halted: bool,
+impl<M> Copy for Program<M> {}
+
+impl<M> Program<M> {
+    fn new(steps: &[Step<M>]) -> Self {
+        let mut program = [(|_, _, _| {}) as Step<M>; 5];
+        program[..steps.len()].copy_from_slice(steps);
+        Self {
+            steps: program,
+            len: steps.len() as u8,
+            condition: None,
+        }
+    }
+    /// steps after `step` are skipped unless the condition it checks holds.
+    fn conditional(steps: &[Step<M>], step: u8) -> Self {
+        Self {
+            condition: Some(step),
+            ..Self::new(steps)
+        }
+    }
+}
+
+/// an instruction partway through its steps, with the fields of its opcode.
+struct InFlight<M> {
+    program: Program<M>,
+    next: u8,
+    /// the opcode it is timed as.
+    opcode: u8,
+    dst: u8,
+    src: u8,
+}
+
+impl<M> std::fmt::Debug for InFlight<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InFlight")
+            .field("opcode", &self.opcode)
+            .field("next", &self.next)
+            .finish()
+    }
+}
+
+struct Dispatch<M>(Box<[Program<M>; 256]>);
+
+impl<M> std::fmt::Debug for Dispatch<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Dispatch")
+    }
+}
+
+/// the state before each of the latest instructions, and the bytes they overwrote in order,
+/// for [`I8080Console::step_back`].
+#[derive(Debug, Default)]
+struct History {
+    depth: usize,
+    entries: VecDeque<(I8080State, Vec<(u16, u8)>)>,
+}
+
+/// why [`I8080Console::run_limited`] returned.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RunResult {
+    Halted,
+    LimitReached,
+    /// no progress across as many instructions as the watchdog allows, at this PC.
+    Stuck(u16),
+    /// the subroutine `step_out()` started in has returned.
+    Returned,
+}
+
+/// why [`I8080Console::run_until_break`] returned.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StopReason {
+    Halted,
+    /// PC reached a breakpoint, whose instruction isn't executed yet.
+    Breakpoint(u16),
+    /// stopped via the handle from [`I8080Console::stop_handle`].
+    Stopped,
+    /// `IN` found no input from the [`InputSource`] of the port, and retries on resuming.
+    InputPending(u8),
+}
+
+/// what [`I8080Console::step`] did.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StepStatus {
+    /// an instruction or an interrupt was served, taking these T-states.
+    Executed(u8),
+    /// halted, waiting for an interrupt.
+    Halted,
+    /// halted with interrupts disabled, so nothing but `reset()` can wake it.
+    HaltedForever,
+}
+
+/// control outputs of the processor. each is shared with the processor, so a device
+/// holding one sees it change.
+#[derive(Debug, Clone)]
+pub struct I8080Signals {
+    pub write: Signal,
+    pub data_in: Signal,
+    pub interrupt_enabled: Signal,
+    pub halted: Signal,
+}
+
+/// member of the 8080 family to emulate.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum I8080Variant {
+    #[default]
+    I8080,
+    /// adds `RIM`, `SIM` and hardware RST 5.5, 6.5 and 7.5 interrupts.
+    I8085,
+}
+
+/// latches of the 256 I/O ports. ports never written read as floating 0xFF.
+#[derive(Debug, Clone)]
+struct IoSpace([u8; 256]);
+
+impl Default for IoSpace {
+    fn default() -> Self {
+        Self([0xff; 256])
+    }
+}
+
+/// devices attached to I/O ports. ports without one fall back to the latches of [`IoSpace`].
+#[derive(Default)]
+struct IoHandlers {
+    input: HashMap<u8, Box<dyn FnMut() -> u8>>,
+    output: HashMap<u8, Box<dyn FnMut(u8)>>,
+}
+
+impl std::fmt::Debug for IoHandlers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IoHandlers")
+            .field("input", &self.input.keys())
+            .field("output", &self.output.keys())
+            .finish()
+    }
+}
+
+/// kind of machine cycle, i.e. of one bus transaction.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum I8080CycleType {
+    OpcodeFetch,
+    MemoryRead,
+    MemoryWrite,
+    StackRead,
+    StackWrite,
+    InputRead,
+    OutputWrite,
+    /// entered by `HLT`, with PC on the address bus and the data bus floating.
+    HaltAcknowledge,
+}
+
+/// a machine cycle and the bus values it carried.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CycleInfo {
+    pub cycle_type: I8080CycleType,
+    pub address: u16,
+    pub data: u8,
+}
+
+/// programmer-visible state of the processor, without memory.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct I8080State {
+    pub a: u8,
+    pub flag: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub halted: bool,
+    pub interrupt_enabled: bool,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum I8080RegisterCode {
     Acc,
     Flag,
@@ -93,12 +402,17 @@ pub enum I8080RegisterCode {
 impl DataRegisterCode for I8080RegisterCode {}
 
 impl I8080RegisterCode {
+    pub const COUNT: usize = 16;
+    /// position in the register file.
+    pub fn index(self) -> usize {
+        self as usize
+    }
     pub fn pair(self) -> [Self; 2] {
         use I8080RegisterCode::*;
         match self {
             Acc | Flag => [Acc, Flag],
             B | C => [B, C],
-            D | E => [D, C],
+            D | E => [D, E],
             W | Z => [W, Z],
             H | L => [H, L],
             other => panic!("No Pair for {:?}!", other),
@@ -106,7 +420,10 @@ impl I8080RegisterCode {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+/// parses the pair names of the mnemonics, e.g. `"PSW"` or `"hl"`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, strum_macros::EnumString)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[strum(ascii_case_insensitive)]
 pub enum I8080RegisterCode16 {
     PSW,
     BC,
@@ -131,7 +448,6 @@ impl I8080RegisterCode16 {
             WZ => [W, Z],
             SP => [SpH, SpL],
             PC => [PcH, PcL],
-            other => panic!("{:?} Can't be Split", other),
         }
     }
 }
@@ -154,40 +470,63 @@ pub enum I8080AluCode {
     ComplementAcc,
 }
 
-pub enum I8080JumpCondition {
-    Anytime,
-    OnNonZero,
-    OnZero,
-    OnNonCarry,
-    OnCarry,
-    OnParityOdd,
-    OnParityEven,
-    OnPlus,
-    OnMinus,
-}
-
 impl<M> I8080Console<M> {
     fn code_reg_as_u8(&self, code: I8080RegisterCode) -> u8 {
-        self.regs
-            .get(&code)
-            .map(|r| r.reg.read())
-            .unwrap_or_else(|| 0)
+        self.regs.regs[code.index()].reg.read()
     }
     fn code_reg16_as_u16(&self, code: I8080RegisterCode16) -> u16 {
         u16::from_be_bytes(code.split().map(|c| self.code_reg_as_u8(c)))
     }
+    fn load_reg16(&mut self, dst: I8080RegisterCode16, bits: u16) {
+        dst.split()
+            .into_iter()
+            .zip(bits.to_be_bytes())
+            .for_each(|(c, x)| self.code_reg_mut(c).reg.load(x));
+    }
     fn load_reg16_from_reg16(&mut self, dst: I8080RegisterCode16, src: I8080RegisterCode16) {
         dst.split().into_iter().zip(src.split()).for_each(|(d, s)| {
             self.code_reg_mut(s).read_to_data();
             self.code_reg_mut(d).load_from_data();
         })
     }
+    pub fn register(&self, code: I8080RegisterCode) -> u8 {
+        self.code_reg_as_u8(code)
+    }
+    /// value of a register pair, e.g. `A << 8 | flags` for PSW.
+    pub fn reg16(&self, code: I8080RegisterCode16) -> u16 {
+        self.code_reg16_as_u16(code)
+    }
+    /// same as [`Self::reg16`], named to go with [`Self::register`].
+    pub fn register16(&self, code: I8080RegisterCode16) -> u16 {
+        self.reg16(code)
+    }
+    pub fn pc(&self) -> u16 {
+        self.code_reg16_as_u16(I8080RegisterCode16::PC)
+    }
+    pub fn sp(&self) -> u16 {
+        self.code_reg16_as_u16(I8080RegisterCode16::SP)
+    }
+    pub fn flags(&self) -> EnumSet<StatusFlag> {
+        self.flag_status()
+    }
+    /// whether `HLT` parked the processor, until an interrupt or `reset()`.
+    pub fn is_halted(&self) -> bool {
+        self.halted.get()
+    }
+    /// load a register pair. the flag byte of PSW is normalized as `POP PSW` does.
+    pub fn set_reg16(&mut self, code: I8080RegisterCode16, value: u16) {
+        let value = match code {
+            I8080RegisterCode16::PSW => {
+                let [a, f] = value.to_be_bytes();
+                u16::from_be_bytes([a, Self::flag_scramble(Self::flag_collect(f))])
+            }
+            _ => value,
+        };
+        self.load_reg16(code, value)
+    }
     #[must_use]
     pub fn code_reg_mut(&mut self, code: I8080RegisterCode) -> &mut I8080DataReg {
-        self.regs.entry(code).or_insert_with(|| I8080DataReg {
-            reg: Default::default(),
-            bus: Rc::clone(&self.data_bus),
-        })
+        &mut self.regs.regs[code.index()]
     }
     fn acc_reg(&mut self) -> &mut I8080DataReg {
         use I8080RegisterCode::Acc;
@@ -198,43 +537,22 @@ impl<M> I8080Console<M> {
         self.code_reg_mut(Tmp)
     }
     fn reg16_increment(&mut self, dst: I8080RegisterCode16) {
-        let [h, l] = dst
-            .split()
-            .map(|c| self.regs.remove(&c).map(|r| r.reg).unwrap_or_default());
-        let mut inc = Register8Pair::new(h, l);
-        inc.increment();
-        dst.split()
-            .into_iter()
-            .zip(inc.split())
-            .for_each(|(c, reg)| {
-                self.regs.insert(
-                    c,
-                    I8080DataReg {
-                        reg,
-                        bus: Rc::clone(&self.data_bus),
-                    },
-                );
-            })
+        self.load_reg16(dst, self.code_reg16_as_u16(dst).wrapping_add(1))
     }
-
     fn reg16_decrement(&mut self, dst: I8080RegisterCode16) {
-        let [h, l] = dst
-            .split()
-            .map(|c| self.regs.remove(&c).map(|r| r.reg).unwrap_or_default());
-        let mut dec = Register8Pair::new(h, l);
-        dec.decrement();
-        dst.split()
-            .into_iter()
-            .zip(dec.split())
-            .for_each(|(c, reg)| {
-                self.regs.insert(
-                    c,
-                    I8080DataReg {
-                        reg,
-                        bus: Rc::clone(&self.data_bus),
-                    },
-                );
-            })
+        self.load_reg16(dst, self.code_reg16_as_u16(dst).wrapping_sub(1))
+    }
+    /// HL += `src`. only the carry flag is affected.
+    fn dad(&mut self, src: I8080RegisterCode16) {
+        use I8080RegisterCode16::HL;
+        let (sum, carry) = self
+            .code_reg16_as_u16(HL)
+            .overflowing_add(self.code_reg16_as_u16(src));
+        self.load_reg16(HL, sum);
+        self.code_reg_mut(I8080RegisterCode::Flag)
+            .reg
+            .masked(Self::flag_decode(StatusFlag::Carry))
+            .load(if carry { !0 } else { 0 });
     }
     pub fn code_reg16_read_to_address(&self, code: I8080RegisterCode16) {
         self.address_bus.set(self.code_reg16_as_u16(code))
@@ -277,12 +595,7 @@ impl<M> I8080Console<M> {
 
     fn flag_status(&self) -> EnumSet<StatusFlag> {
         use I8080RegisterCode::Flag;
-        Self::flag_collect(
-            self.regs
-                .get(&Flag)
-                .map(|r| r.reg.read())
-                .unwrap_or_default(),
-        )
+        Self::flag_collect(self.code_reg_as_u8(Flag))
     }
 
     fn flag_decode(flag: StatusFlag) -> u8 {
@@ -324,12 +637,12 @@ impl<M> I8080Console<M> {
             RotateLeft => Box::new(Rotator::rotate_left()),
             RotateRight => Box::new(Rotator::rotate_right()),
             RotateLeftThroughCarry => Box::new(
-                Rotator::rotate_right()
+                Rotator::rotate_left()
                     .through_carry()
                     .carried(self.flag_status().contains(Carry)),
             ),
             RotateRightThroughCarry => Box::new(
-                Rotator::rotate_left()
+                Rotator::rotate_right()
                     .through_carry()
                     .carried(self.flag_status().contains(Carry)),
             ),
@@ -340,8 +653,14 @@ impl<M> I8080Console<M> {
     fn alu_op(&mut self, alu: Box<dyn ALU<Data = u8, Flag = StatusFlag>>) {
         use I8080RegisterCode::Flag;
         let (res, flag) = alu.op(self.acc_reg().reg.read(), self.tmp_reg().reg.read());
-        self.data_bus.set(res);
-        self.code_reg_mut(Flag).reg.load(Self::flag_scramble(flag));
+        self.regs.bus.set(res);
+        let kept = (!alu.affected_flags())
+            .into_iter()
+            .fold(0, |acc, f| acc | Self::flag_decode(f));
+        self.code_reg_mut(Flag)
+            .reg
+            .masked(!kept)
+            .load(Self::flag_scramble(flag));
     }
 
     pub fn alu_with_reg(&mut self, alu: I8080AluCode, rhs: I8080RegisterCode) {
@@ -408,21 +727,331 @@ impl<M> I8080Console<M> {
             let (flag, set) = Self::flag_objected_by(cond);
             self.flag_status().contains(flag) == set
         };
-        match cond {
+        let satisfied = match cond {
             Anytime => true,
             other => set(other),
+        };
+        self.branch_taken = satisfied;
+        satisfied
+    }
+
+    pub fn set_timing(&mut self, timing: I8080Timing) {
+        self.timing = timing;
+    }
+
+    pub fn timing(&self) -> &I8080Timing {
+        &self.timing
+    }
+
+    /// T-states spent since the console was made.
+    pub fn total_cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// call `f` on every instruction, after its opcode is fetched and before it takes effect.
+    pub fn set_trace(&mut self, f: Box<dyn FnMut(TraceEvent)>) {
+        self.trace = Some(Trace(f));
+    }
+
+    /// the registers in one line, e.g. `A=12 F=[SZ-A-P-C] BC=3456 DE=789A HL=BCDE SP=F000
+    /// PC=0100 cyc=42`. cleared flags show as `-` like the fixed bits.
+    pub fn state_line(&self) -> String {
+        let state = self.snapshot();
+        let flags: String = "SZ-A-P-C"
+            .chars()
+            .enumerate()
+            .map(|(i, name)| match state.flag & 0x80 >> i {
+                0 => '-',
+                _ => name,
+            })
+            .collect();
+        format!(
+            "A={:02X} F=[{flags}] BC={:02X}{:02X} DE={:02X}{:02X} HL={:02X}{:02X} SP={:04X} PC={:04X} cyc={}",
+            state.a, state.b, state.c, state.d, state.e, state.h, state.l, state.sp, state.pc,
+            self.cycles
+        )
+    }
+
+    /// keep what `step_back()` needs to undo the latest `depth` instructions. registers and
+    /// memory are restored; I/O, cycle counts and interrupts served meanwhile are not.
+    /// writes are recorded only where [`Memory::peek`] can read the old byte, so memory
+    /// behind devices is left as it is.
+    pub fn enable_history(&mut self, depth: usize) {
+        self.history = Some(History {
+            depth,
+            entries: VecDeque::with_capacity(depth),
+        });
+    }
+
+    /// start counting executions of each opcode, from zero.
+    pub fn enable_profiling(&mut self) {
+        self.opcode_histogram = Some([0; 256]);
+    }
+
+    /// executions of each opcode since `enable_profiling()`, `None` unless profiling.
+    pub fn opcode_counts(&self) -> Option<&[u64; 256]> {
+        self.opcode_histogram.as_ref()
+    }
+
+    /// instructions executed since the last `reset()`. served interrupts don't count.
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions_executed
+    }
+
+    /// pace execution with `clock`, which is told the T-states of every instruction.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = Some(clock);
+    }
+
+    fn spend(&mut self, states: u8) {
+        self.cycles += states as u64;
+        if let Some(clock) = &mut self.clock {
+            clock.wait(states as u64);
+        }
+        for peripheral in &mut self.peripherals {
+            peripheral.tick(states as u64);
         }
     }
 
-    /// special
-    pub fn enable_interrupt(&mut self) {}
+    /// attach `peripheral`, which is ticked along and consulted on `IN` and `OUT`
+    /// before the port handlers.
+    pub fn add_peripheral(&mut self, peripheral: Box<dyn Peripheral>) {
+        self.peripherals.push(peripheral);
+    }
 
     /// special
-    pub fn disable_interrupt(&mut self) {}
+    pub fn enable_interrupt(&mut self) {
+        self.interrupt_enabled.set(true);
+    }
 
     /// special
+    pub fn disable_interrupt(&mut self) {
+        self.interrupt_enabled.set(false);
+    }
+
+    /// also switches to the timing table of the variant.
+    pub fn set_variant(&mut self, variant: I8080Variant) {
+        self.variant = variant;
+        self.timing = match variant {
+            I8080Variant::I8080 => I8080Timing::I8080,
+            I8080Variant::I8085 => I8080Timing::I8085,
+        };
+    }
+
+    pub fn variant(&self) -> I8080Variant {
+        self.variant
+    }
+
+    /// 8085 only. `SIM`, sets RST masks by Acc when its bit 3 is set,
+    /// and resets pending RST 7.5 when its bit 4 is set.
+    pub fn set_interrupt_mask(&mut self) {
+        let acc = self.acc_reg().reg.read();
+        if acc & 0x08 != 0 {
+            self.rst_unmasked = !acc & 0x07;
+        }
+        if acc & 0x10 != 0 {
+            self.rst_pending &= !0x04;
+        }
+    }
+
+    /// 8085 only. `RIM`, reads RST masks, interrupt enable and pending RSTs to Acc.
+    pub fn read_interrupt_mask(&mut self) {
+        let mask = !self.rst_unmasked & 0x07;
+        let acc = mask | (self.interrupt_enabled.get() as u8) << 3 | self.rst_pending << 4;
+        self.acc_reg().reg.load(acc);
+    }
+
+    /// 8085 only. RST 5.5 is ignored while masked.
+    pub fn trigger_rst55(&mut self) {
+        if self.variant == I8080Variant::I8085 {
+            self.rst_pending |= self.rst_unmasked & 0x01;
+        }
+    }
+
+    /// 8085 only. RST 6.5 is ignored while masked.
+    pub fn trigger_rst65(&mut self) {
+        if self.variant == I8080Variant::I8085 {
+            self.rst_pending |= self.rst_unmasked & 0x02;
+        }
+    }
+
+    /// 8085 only. RST 7.5 is latched even while masked, and served once unmasked.
+    pub fn trigger_rst75(&mut self) {
+        if self.variant == I8080Variant::I8085 {
+            self.rst_pending |= 0x04;
+        }
+    }
+
+    /// the registers, PC, SP, halt and INTE, for [`Self::restore`].
+    pub fn snapshot(&self) -> I8080State {
+        use I8080RegisterCode::*;
+        use I8080RegisterCode16::{PC, SP};
+        I8080State {
+            a: self.code_reg_as_u8(Acc),
+            flag: self.code_reg_as_u8(Flag),
+            b: self.code_reg_as_u8(B),
+            c: self.code_reg_as_u8(C),
+            d: self.code_reg_as_u8(D),
+            e: self.code_reg_as_u8(E),
+            h: self.code_reg_as_u8(H),
+            l: self.code_reg_as_u8(L),
+            sp: self.code_reg16_as_u16(SP),
+            pc: self.code_reg16_as_u16(PC),
+            halted: self.halted.get(),
+            interrupt_enabled: self.interrupt_enabled.get(),
+        }
+    }
+
+    /// load a state taken by [`Self::snapshot`]. memory is left as it is.
+    pub fn restore(&mut self, state: &I8080State) {
+        use I8080RegisterCode::*;
+        use I8080RegisterCode16::{PC, SP};
+        for (code, x) in [
+            (Acc, state.a),
+            (Flag, state.flag),
+            (B, state.b),
+            (C, state.c),
+            (D, state.d),
+            (E, state.e),
+            (H, state.h),
+            (L, state.l),
+        ] {
+            self.code_reg_mut(code).reg.load(x);
+        }
+        self.load_reg16(SP, state.sp);
+        self.load_reg16(PC, state.pc);
+        self.halted.set(state.halted);
+        self.interrupt_enabled.set(state.interrupt_enabled);
+    }
+
+    /// fill A, flags, B to L and SP from a PRNG seeded by `seed`, so that runs start
+    /// from varied but reproducible states. PC is left for the program's entry.
+    pub fn randomize_registers(&mut self, seed: u64) {
+        use I8080RegisterCode::*;
+        use I8080RegisterCode16::{PSW, SP};
+        // splitmix64
+        let mut state = seed;
+        let mut random = || {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        let [a, f, b, c, d, e, h, l] = random().to_be_bytes();
+        self.set_reg16(PSW, u16::from_be_bytes([a, f]));
+        for (code, x) in [(B, b), (C, c), (D, d), (E, e), (H, h), (L, l)] {
+            self.code_reg_mut(code).reg.load(x);
+        }
+        self.load_reg16(SP, random() as u16);
+    }
+
+    /// the memory, shared with the processor. lock it to save or load its contents.
+    pub fn memory(&self) -> &Arc<Mutex<M>> {
+        &self.memory
+    }
+
+    /// WR, DBIN, INTE and HLTA, for devices to watch.
+    pub fn signals(&self) -> I8080Signals {
+        I8080Signals {
+            write: Rc::clone(&self.write),
+            data_in: Rc::clone(&self.data_in),
+            interrupt_enabled: Rc::clone(&self.interrupt_enabled),
+            halted: Rc::clone(&self.halted),
+        }
+    }
+
+    /// `EXX` of the Z80: swap BC, DE and HL with their shadows.
+    #[cfg(feature = "z80")]
+    pub fn exchange_register_banks(&mut self) {
+        use I8080RegisterCode::*;
+        let shadow = &mut self.shadow;
+        for (main, alt) in [
+            (B, &mut shadow.b),
+            (C, &mut shadow.c),
+            (D, &mut shadow.d),
+            (E, &mut shadow.e),
+            (H, &mut shadow.h),
+            (L, &mut shadow.l),
+        ] {
+            let reg = &mut self.regs.regs[main.index()].reg;
+            let x = reg.read();
+            reg.load(*alt);
+            *alt = x;
+        }
+    }
+
+    /// the shadow registers of the Z80.
+    #[cfg(feature = "z80")]
+    pub fn shadow_registers(&self) -> &ShadowRegisters {
+        &self.shadow
+    }
+
+    /// the value on the data bus, left by the last bus cycle or register transfer.
+    pub fn data_bus(&self) -> u8 {
+        self.regs.bus.get()
+    }
+
+    /// put `value` on the data bus, e.g. for the next `store()` to write.
+    pub fn set_data_bus(&self, value: u8) {
+        self.regs.bus.set(value)
+    }
+
+    /// the value on the address bus, left by the last bus cycle.
+    pub fn address_bus(&self) -> u16 {
+        self.address_bus.get()
+    }
+
+    /// address `reset()` starts execution from. defaults to 0x0000.
+    pub fn set_reset_vector(&mut self, address: u16) {
+        self.reset_vector = address;
+    }
+
+    /// the RESET input: clear the registers, jump to the reset vector, disable interrupts and
+    /// leave the halt state. the flag byte keeps its always-set bit 1. memory is untouched.
+    pub fn reset(&mut self) {
+        for reg in &mut self.regs.regs {
+            reg.reg.load(0);
+        }
+        self.code_reg_mut(I8080RegisterCode::Flag)
+            .reg
+            .load(Self::flag_scramble(EnumSet::empty()));
+        self.load_reg16(I8080RegisterCode16::PC, self.reset_vector);
+        self.halted.set(false);
+        self.interrupt_enabled.set(false);
+        self.rst_pending = 0;
+        self.rst_unmasked = 0;
+        self.instructions_executed = 0;
+        self.bdos = false;
+        self.in_flight = None;
+        #[cfg(feature = "z80")]
+        {
+            self.shadow = ShadowRegisters::default();
+        }
+    }
+
+    fn record_cycle(&mut self, default: I8080CycleType) {
+        use I8080CycleType::*;
+        let cycle_type = self.cycle_type.take().unwrap_or(default);
+        let write = matches!(cycle_type, MemoryWrite | StackWrite | OutputWrite);
+        self.write.set(write);
+        self.write_cycles += write as u64;
+        self.data_in.set(!write && cycle_type != HaltAcknowledge);
+        self.last_cycle = Some(CycleInfo {
+            cycle_type,
+            address: self.address_bus.get(),
+            data: self.regs.bus.get(),
+        });
+    }
+
+    /// the halt acknowledge cycle. the address bus holds PC, i.e. the instruction following
+    /// `HLT`, the data bus floats and reads as 0xFF, and neither WR nor DBIN is asserted.
+    /// the bus stays so until an interrupt or a reset.
     pub fn halt(&mut self) {
-        self.halted = true;
+        self.halted.set(true);
+        self.code_reg16_read_to_address(I8080RegisterCode16::PC);
+        self.regs.bus.set(0xff);
+        self.record_cycle(I8080CycleType::HaltAcknowledge);
     }
 
     /// special
@@ -467,6 +1096,31 @@ impl<M> I8080Console<M> {
             _ => unreachable!(),
         }
     }
+
+    /// `CALL`, conditional ones and `RST`, which leave a return address.
+    fn is_call_opcode(opcode: u8) -> bool {
+        let (op, dst, src) = decompose(opcode);
+        match (op, dst, src) {
+            (3, _, 4) | (3, _, 7) => true,
+            (3, dst, 5) => dst % 2 == 1,
+            _ => false,
+        }
+    }
+
+    /// `RET` and its conditional ones, including the undocumented 0xD9.
+    fn is_return_opcode(opcode: u8) -> bool {
+        let (op, dst, src) = decompose(opcode);
+        matches!((op, dst, src), (3, _, 0) | (3, 1 | 3, 1))
+    }
+
+    /// `JMP`, `RET`, their conditional ones and `PCHL`, which leave no return address.
+    fn is_branch_opcode(opcode: u8) -> bool {
+        let (op, dst, src) = decompose(opcode);
+        matches!(
+            (op, dst, src),
+            (3, _, 0) | (3, _, 2) | (3, 0 | 1, 3) | (3, 1 | 3 | 5, 1)
+        )
+    }
 }
 
 impl<M: Memory<Data = u8, Address = u16>> I8080Console<M> {
@@ -483,62 +1137,154 @@ impl<M: Memory<Data = u8, Address = u16>> I8080Console<M> {
     fn fetch_operand_to_wz(&mut self) {
         use I8080RegisterCode::{W, Z};
         // little endian
-        self.fetch_instruction();
-        self.code_reg_mut(Z).load_from_data();
-        self.fetch_instruction();
-        self.code_reg_mut(W).load_from_data();
+        self.move_reg_immediate(Z);
+        self.move_reg_immediate(W);
     }
-    pub fn fetch_instruction(&mut self) {
-        use I8080RegisterCode16::PC;
-        self.code_reg16_read_to_address(PC);
+    fn fetch_at_wz(&mut self, dst: I8080RegisterCode) {
+        use I8080RegisterCode16::WZ;
+        self.code_reg16_read_to_address(WZ);
         self.fetch();
-        self.reg16_increment(PC);
-    }
-
-    pub fn move_hl_mem_to_reg(&mut self, dst: I8080RegisterCode) {
-        self.fetch_hl();
         self.code_reg_mut(dst).load_from_data();
     }
-
-    pub fn store_reg_to_hl_mem(&mut self, src: I8080RegisterCode) {
+    fn store_at_wz(&mut self, src: I8080RegisterCode) {
+        use I8080RegisterCode16::WZ;
+        self.code_reg16_read_to_address(WZ);
         self.code_reg_mut(src).read_to_data();
-        self.store_hl();
-    }
-
-    pub fn move_reg_immediate(&mut self, dst: I8080RegisterCode) {
-        self.fetch_instruction();
-        self.code_reg_mut(dst).load_from_data();
+        self.store();
     }
-
-    pub fn store_hl_immediate(&mut self) {
-        self.fetch_instruction();
-        self.store_hl();
+    fn jump_to_wz(&mut self, cond: I8080JumpCondition) {
+        use I8080RegisterCode16::{PC, WZ};
+        if self.satisfying_condition(cond) {
+            self.load_reg16_from_reg16(PC, WZ);
+        }
     }
 
-    pub fn move_reg16_immediate(&mut self, dst: I8080RegisterCode16) {
-        let [h, l] = dst.split();
-        self.fetch_instruction();
-        self.code_reg_mut(h).load_from_data();
-        self.fetch_instruction();
-        self.code_reg_mut(l).load_from_data();
+    /// serve the most prior pending RST of 8085 if interrupts are enabled,
+    /// and returns T-states it took.
+    fn accept_hardware_rst(&mut self) -> Option<u8> {
+        self.begin_hardware_rst().then(|| self.finish().unwrap())
+    }
+    /// put the most prior pending RST of 8085 in flight if interrupts are enabled.
+    fn begin_hardware_rst(&mut self) -> bool {
+        use I8080RegisterCode16::PC;
+        let serviceable = self.rst_pending & self.rst_unmasked;
+        if !self.interrupt_enabled.get() || serviceable == 0 {
+            return false;
+        }
+        let bit = 7 - serviceable.leading_zeros() as u8;
+        self.rst_pending &= !(1 << bit);
+        self.interrupt_enabled.set(false);
+        self.halted.set(false);
+        let program = Program::new(&[
+            |_, _, _| {},
+            |c, _, _| c.push_from(PC.split()[0]),
+            |c, bit, _| {
+                c.push_from(PC.split()[1]);
+                // RST 5.5, 6.5 and 7.5 vector to 0x2c, 0x34 and 0x3c.
+                c.load_reg16(PC, 0x2c + 8 * bit as u16);
+            },
+        ]);
+        self.in_flight = Some(InFlight {
+            program,
+            next: 0,
+            opcode: 0xff,
+            dst: bit,
+            src: 0,
+        });
+        true
+    }
+    fn store_stack(&mut self) {
+        use I8080RegisterCode16::SP;
+        self.code_reg16_read_to_address(SP);
+        self.cycle_type = Some(I8080CycleType::StackWrite);
+        self.store()
+    }
+    fn fetch_stack(&mut self) {
+        use I8080RegisterCode16::SP;
+        self.code_reg16_read_to_address(SP);
+        self.cycle_type = Some(I8080CycleType::StackRead);
+        self.fetch()
+    }
+    fn fetch_stack_to(&mut self, dst: I8080RegisterCode) {
+        self.fetch_stack();
+        self.code_reg_mut(dst).load_from_data();
+    }
+    fn store_stack_from(&mut self, src: I8080RegisterCode) {
+        self.code_reg_mut(src).read_to_data();
+        self.store_stack();
+    }
+    fn push_from(&mut self, src: I8080RegisterCode) {
+        use I8080RegisterCode16::SP;
+        self.reg16_decrement(SP);
+        self.store_stack_from(src);
+    }
+    fn pop_to(&mut self, dst: I8080RegisterCode) {
+        use I8080RegisterCode16::SP;
+        self.fetch_stack_to(dst);
+        self.reg16_increment(SP);
+    }
+    /// pop the high register of `code`, after its low one.
+    fn pop_high(&mut self, code: I8080RegisterCode16) {
+        let [h, l] = code.split();
+        self.pop_to(h);
+        if code == I8080RegisterCode16::PSW {
+            // bit 1 always reads 1, and bits 3 and 5 always 0.
+            let flags = Self::flag_collect(self.code_reg_as_u8(l));
+            self.code_reg_mut(l).reg.load(Self::flag_scramble(flags));
+        }
+    }
+    pub fn fetch_instruction(&mut self) {
+        use I8080RegisterCode16::PC;
+        self.code_reg16_read_to_address(PC);
+        match self.staged.pop_front() {
+            Some(byte) => {
+                self.regs.bus.set(byte);
+                self.record_cycle(I8080CycleType::MemoryRead);
+            }
+            None => self.fetch(),
+        }
+        self.reg16_increment(PC);
+    }
+
+    pub fn move_hl_mem_to_reg(&mut self, dst: I8080RegisterCode) {
+        self.fetch_hl();
+        self.code_reg_mut(dst).load_from_data();
+    }
+
+    pub fn store_reg_to_hl_mem(&mut self, src: I8080RegisterCode) {
+        self.code_reg_mut(src).read_to_data();
+        self.store_hl();
+    }
+
+    pub fn move_reg_immediate(&mut self, dst: I8080RegisterCode) {
+        self.fetch_instruction();
+        self.code_reg_mut(dst).load_from_data();
+    }
+
+    pub fn store_hl_immediate(&mut self) {
+        self.fetch_instruction();
+        self.store_hl();
+    }
+
+    pub fn move_reg16_immediate(&mut self, dst: I8080RegisterCode16) {
+        let [h, l] = dst.split();
+        // little endian
+        self.fetch_instruction();
+        self.code_reg_mut(l).load_from_data();
+        self.fetch_instruction();
+        self.code_reg_mut(h).load_from_data();
     }
 
     /// practically, dst is Acc
     pub fn move_reg_direct(&mut self, dst: I8080RegisterCode) {
-        use I8080RegisterCode16::WZ;
         self.fetch_operand_to_wz();
-        self.code_reg16_read_to_address(WZ);
-        self.fetch();
-        self.code_reg_mut(dst).load_from_data();
+        self.fetch_at_wz(dst);
     }
 
     /// practically, src is Acc
     pub fn store_reg_direct(&mut self, src: I8080RegisterCode) {
-        use I8080RegisterCode16::WZ;
         self.fetch_operand_to_wz();
-        self.code_reg16_read_to_address(WZ);
-        self.code_reg_mut(src).read_to_data();
-        self.store();
+        self.store_at_wz(src);
     }
 
     /// practically, dst is HL
@@ -546,35 +1292,19 @@ impl<M: Memory<Data = u8, Address = u16>> I8080Console<M> {
         use I8080RegisterCode16::WZ;
         let [h, l] = dst.split();
         self.fetch_operand_to_wz();
-        self.code_reg16_read_to_address(WZ);
-        self.fetch();
-        self.code_reg_mut(l).load_from_data();
-
+        self.fetch_at_wz(l);
         self.reg16_increment(WZ);
-
-        self.code_reg16_read_to_address(WZ);
-        self.fetch();
-        self.code_reg_mut(h).load_from_data();
+        self.fetch_at_wz(h);
     }
 
     /// practically, src is HL
     pub fn store_reg16_direct(&mut self, src: I8080RegisterCode16) {
-        use I8080RegisterCode::{W, Z};
         use I8080RegisterCode16::WZ;
         let [h, l] = src.split();
-        self.fetch_instruction();
-        self.code_reg_mut(W).load_from_data();
-        self.fetch_instruction();
-        self.code_reg_mut(Z).load_from_data();
-        self.code_reg16_read_to_address(WZ);
-        self.code_reg_mut(h).read_to_data();
-        self.store();
-
+        self.fetch_operand_to_wz();
+        self.store_at_wz(l);
         self.reg16_increment(WZ);
-
-        self.code_reg16_read_to_address(WZ);
-        self.code_reg_mut(l).read_to_data();
-        self.store();
+        self.store_at_wz(h);
     }
 
     pub fn move_indirect(&mut self, dst: I8080RegisterCode, src: I8080RegisterCode16) {
@@ -589,18 +1319,25 @@ impl<M: Memory<Data = u8, Address = u16>> I8080Console<M> {
         self.store();
     }
 
-    pub fn alu_with_mem(&mut self, alu: I8080AluCode) {
+    fn fetch_hl_to_tmp(&mut self) {
         self.fetch_hl();
         self.tmp_reg().load_from_data();
+    }
+
+    fn alu_to_hl_mem(&mut self, alu: I8080AluCode) {
+        self.alu_op(self.alu_from_code(alu));
+        self.store_hl();
+    }
+
+    pub fn alu_with_mem(&mut self, alu: I8080AluCode) {
+        self.fetch_hl_to_tmp();
         self.alu_op(self.alu_from_code(alu));
         self.acc_reg().load_from_data();
     }
 
     pub fn alu_with_mem_to_mem(&mut self, alu: I8080AluCode) {
-        self.fetch_hl();
-        self.tmp_reg().load_from_data();
-        self.alu_op(self.alu_from_code(alu));
-        self.store();
+        self.fetch_hl_to_tmp();
+        self.alu_to_hl_mem(alu);
     }
 
     pub fn alu_with_immediate(&mut self, alu: I8080AluCode) {
@@ -612,8 +1349,7 @@ impl<M: Memory<Data = u8, Address = u16>> I8080Console<M> {
 
     pub fn cmp_with_mem(&mut self) {
         use I8080AluCode::Sub;
-        self.fetch_hl();
-        self.tmp_reg().load_from_data();
+        self.fetch_hl_to_tmp();
         self.alu_op(self.alu_from_code(Sub));
     }
 
@@ -625,111 +1361,141 @@ impl<M: Memory<Data = u8, Address = u16>> I8080Console<M> {
     }
 
     pub fn jump_immediate(&mut self, cond: I8080JumpCondition) {
-        use I8080RegisterCode16::{PC, WZ};
         self.fetch_operand_to_wz();
-        if self.satisfying_condition(cond) {
-            self.load_reg16_from_reg16(PC, WZ);
-        }
+        self.jump_to_wz(cond);
     }
 
     pub fn call_immediate(&mut self, cond: I8080JumpCondition) {
-        use I8080RegisterCode16::{PC, SP, WZ};
+        use I8080RegisterCode16::{PC, WZ};
         self.fetch_operand_to_wz();
         if self.satisfying_condition(cond) {
             let [pch, pcl] = PC.split();
-            self.reg16_increment(SP);
-            self.code_reg_mut(pch).read_to_data();
-            self.code_reg16_read_to_address(SP);
-            self.store();
-
-            self.reg16_decrement(SP);
-            self.code_reg_mut(pcl).read_to_data();
-            self.code_reg16_read_to_address(SP);
-            self.store();
-
+            self.push_from(pch);
+            self.push_from(pcl);
             self.load_reg16_from_reg16(PC, WZ);
         }
     }
     pub fn ret(&mut self, cond: I8080JumpCondition) {
         use I8080RegisterCode::{W, Z};
-        use I8080RegisterCode16::{PC, SP, WZ};
+        use I8080RegisterCode16::{PC, WZ};
         if self.satisfying_condition(cond) {
-            self.code_reg16_read_to_address(SP);
-            self.fetch();
-            self.code_reg_mut(Z).load_from_data();
-            self.reg16_increment(SP);
+            self.pop_to(Z);
+            self.pop_to(W);
+            self.load_reg16_from_reg16(PC, WZ);
+        }
+    }
 
-            self.code_reg16_read_to_address(SP);
-            self.fetch();
-            self.code_reg_mut(W).load_from_data();
-            self.reg16_increment(SP);
+    /// load a CP/M `.COM` image at 0x0100 and start there, with a return address of 0 pushed
+    /// below 0xFF00. `CALL 5` then serves BDOS functions 0, 2 and 9, printing through the
+    /// output handler of [`CPM_CONSOLE_PORT`], and reaching 0x0000 halts as warm boot.
+    /// `reset()` turns the BDOS off again.
+    pub fn load_com(&mut self, bytes: &[u8]) {
+        use I8080RegisterCode16::{PC, SP};
+        // flashed, past read-only ranges and write watches.
+        let mut memory = self.memory.lock().unwrap();
+        for (i, &byte) in bytes.iter().enumerate() {
+            memory.poke(0x0100u16.wrapping_add(i as u16), byte);
+        }
+        memory.poke(0xfefe, 0x00);
+        memory.poke(0xfeff, 0x00);
+        drop(memory);
+        self.load_reg16(SP, 0xfefe);
+        self.load_reg16(PC, 0x0100);
+        self.bdos = true;
+    }
 
-            self.load_reg16_from_reg16(PC, WZ);
+    /// serve the BDOS call or warm boot at PC if any, as `load_com()` sets up. it is then
+    /// put in flight as the `RET` or `HLT` that ends it, without fetching that.
+    fn begin_bdos_call(&mut self) -> bool {
+        use I8080RegisterCode::{C, E};
+        use I8080RegisterCode16::{DE, PC};
+        let warm_boot = match self.code_reg16_as_u16(PC) {
+            0x0000 => true,
+            0x0005 => self.code_reg_as_u8(C) == 0,
+            _ => return false,
+        };
+        let opcode = if warm_boot {
+            0x76
+        } else {
+            match self.code_reg_as_u8(C) {
+                2 => self.console_output(self.code_reg_as_u8(E)),
+                // gives up after the whole address space if no `$` turns up.
+                9 => {
+                    let start = self.code_reg16_as_u16(DE);
+                    for offset in 0..=u16::MAX {
+                        let byte = self.memory.lock().unwrap().read(start.wrapping_add(offset));
+                        if byte == b'$' {
+                            break;
+                        }
+                        self.console_output(byte);
+                    }
+                }
+                _ => {}
+            }
+            0xc9
+        };
+        let (_, dst, src) = decompose(opcode);
+        self.in_flight = Some(InFlight {
+            program: self.program_of(opcode),
+            next: 0,
+            opcode,
+            dst,
+            src,
+        });
+        true
+    }
+
+    fn console_output(&mut self, byte: u8) {
+        if let Some(handler) = self.io_handlers.output.get_mut(&CPM_CONSOLE_PORT) {
+            handler(byte);
+        }
+    }
+
+    /// the INTR input with `RST rst_vector` on the data bus, taken modulo 8. ignored unless
+    /// interrupts are enabled, and disables them once accepted, as the chip does until the
+    /// handler runs `EI`.
+    pub fn interrupt(&mut self, rst_vector: u8) {
+        use I8080RegisterCode16::PC;
+        if !self.interrupt_enabled.get() {
+            return;
         }
+        let rst_vector = rst_vector & 0x7;
+        self.finish();
+        self.interrupt_enabled.set(false);
+        self.halted.set(false);
+        self.push_reg16(PC);
+        self.load_reg16(PC, rst_vector as u16 * 8);
+        self.spend(self.timing.states(0xc7 | rst_vector << 3, false));
     }
 
     /// restart, that equals `call n*8`
     pub fn restart(&mut self, n: u8) {
-        use I8080RegisterCode16::{PC, SP, WZ};
-        let [pch, pcl] = [0, n * 8];
-        self.reg16_decrement(SP);
-        self.data_bus.set(pch);
-        self.code_reg16_read_to_address(SP);
-        self.store();
-
-        self.reg16_decrement(SP);
-        self.data_bus.set(pcl);
-        self.code_reg16_read_to_address(SP);
-        self.store();
-
-        self.load_reg16_from_reg16(PC, WZ);
+        use I8080RegisterCode16::PC;
+        self.push_reg16(PC);
+        self.load_reg16(PC, n as u16 * 8);
     }
 
     pub fn push_reg16(&mut self, code: I8080RegisterCode16) {
-        use I8080RegisterCode16::SP;
         let [h, l] = code.split();
-        self.reg16_decrement(SP);
-        self.code_reg_mut(h).read_to_data();
-        self.code_reg16_read_to_address(SP);
-        self.store();
-
-        self.reg16_decrement(SP);
-        self.code_reg_mut(l).read_to_data();
-        self.code_reg16_read_to_address(SP);
-        self.store();
+        self.push_from(h);
+        self.push_from(l);
     }
 
     pub fn pop_reg16(&mut self, code: I8080RegisterCode16) {
-        use I8080RegisterCode16::SP;
-        let [h, l] = code.split();
-        self.code_reg16_read_to_address(SP);
-        self.fetch();
-        self.code_reg_mut(h).load_from_data();
-        self.reg16_increment(SP);
-
-        self.code_reg16_read_to_address(SP);
-        self.fetch();
-        self.code_reg_mut(l).load_from_data();
-        self.reg16_increment(SP);
+        let [_, l] = code.split();
+        self.pop_to(l);
+        self.pop_high(code);
     }
 
     /// special
     pub fn exchange_stack_top_with_hl(&mut self) {
         use I8080RegisterCode::{H, L, W, Z};
         use I8080RegisterCode16::{HL, SP, WZ};
-
-        self.code_reg16_read_to_address(SP);
-        self.fetch();
-        self.code_reg_mut(Z).load_from_data();
-        self.code_reg_mut(L).read_to_data();
-        self.store();
+        self.fetch_stack_to(Z);
+        self.store_stack_from(L);
         self.reg16_increment(SP);
-
-        self.code_reg16_read_to_address(SP);
-        self.fetch();
-        self.code_reg_mut(W).load_from_data();
-        self.code_reg_mut(H).read_to_data();
-        self.store();
+        self.fetch_stack_to(W);
+        self.store_stack_from(H);
         self.reg16_decrement(SP);
         self.load_reg16_from_reg16(HL, WZ);
     }
@@ -737,222 +1503,836 @@ impl<M: Memory<Data = u8, Address = u16>> I8080Console<M> {
     /// special
     pub fn input(&mut self) {
         self.fetch_instruction();
-        let acc = match self.data_bus.get() {
-            _ => {
-                let mut buf = [0];
-                std::io::stdin().read_exact(&mut buf).unwrap();
-                buf[0]
-            }
+        self.input_cycle();
+    }
+
+    /// the input cycle of `IN`, from the port just fetched onto the data bus.
+    fn input_cycle(&mut self) {
+        let port = self.regs.bus.get();
+        self.address_bus.set(u16::from_be_bytes([port, port]));
+        let claimed = self.peripherals.iter_mut().find_map(|p| p.on_in(port));
+        let claimed = match (claimed, self.input_sources.get_mut(&port)) {
+            (None, Some(source)) => match source.poll(port) {
+                None => return self.wait_for_input(port),
+                data => data,
+            },
+            (claimed, _) => claimed,
+        };
+        let data = match (claimed, self.io_handlers.input.get_mut(&port)) {
+            (Some(data), _) => data,
+            (None, Some(handler)) => handler(),
+            (None, None) => self.io_space.0[port as usize],
         };
-        self.data_bus.set(acc);
+        self.regs.bus.set(data);
+        self.record_cycle(I8080CycleType::InputRead);
         self.acc_reg().load_from_data()
     }
 
+    /// go back to the `IN` just fetched, to retry it as the next instruction.
+    fn wait_for_input(&mut self, port: u8) {
+        use I8080RegisterCode16::PC;
+        self.record_cycle(I8080CycleType::InputRead);
+        self.load_reg16(PC, self.code_reg16_as_u16(PC).wrapping_sub(2));
+        self.input_pending = Some(port);
+    }
+
     /// special
     pub fn output(&mut self) {
         self.fetch_instruction();
+        self.output_cycle();
+    }
+
+    /// the output cycle of `OUT`, to the port just fetched onto the data bus.
+    fn output_cycle(&mut self) {
+        let port = self.regs.bus.get();
+        self.address_bus.set(u16::from_be_bytes([port, port]));
         self.acc_reg().read_to_data();
-        println!("{}", self.data_bus.get() as char);
+        self.io_space.0[port as usize] = self.regs.bus.get();
+        self.record_cycle(I8080CycleType::OutputWrite);
+        let data = self.regs.bus.get();
+        for peripheral in &mut self.peripherals {
+            peripheral.on_out(port, data);
+        }
+        if let Some(handler) = self.io_handlers.output.get_mut(&port) {
+            handler(self.regs.bus.get());
+        }
     }
 
-    pub fn execute(&mut self) {
+    /// the steps of `opcode`, to fill the dispatch table with. the twelve undocumented
+    /// opcodes (`// <= unspecified`) behave as their documented twins on the chip: 0x08-0x38
+    /// in steps of 8 as `NOP`, 0xCB as `JMP`, 0xD9 as `RET` and 0xDD, 0xED, 0xFD as `CALL`.
+    fn program(opcode: u8) -> Program<M> {
         use I8080AluCode::*;
         use I8080JumpCondition::*;
         use I8080RegisterCode::*;
         use I8080RegisterCode16::*;
-        self.fetch_instruction();
-        self.code_reg_mut(Inst).load_from_data();
-        let (op, dst, src) = {
-            let inst = self.code_reg_mut(Inst).reg.read();
-            ((inst >> 6), (inst >> 3) & 0x7, inst & 0x7)
-        };
+        let fetched: Step<M> = |_, _, _| {};
+        let one = |step: Step<M>| Program::new(&[step]);
+        let two = |step: Step<M>| Program::new(&[fetched, step]);
+        let ret: Program<M> = Program::conditional(
+            &[
+                |c, _, _| _ = c.satisfying_condition(Anytime),
+                |c, _, _| c.pop_to(Z),
+                |c, _, _| {
+                    c.pop_to(W);
+                    c.load_reg16_from_reg16(PC, WZ);
+                },
+            ],
+            0,
+        );
+        let jump: Program<M> = Program::new(&[
+            fetched,
+            |c, _, _| c.move_reg_immediate(Z),
+            |c, _, _| {
+                c.move_reg_immediate(W);
+                c.jump_to_wz(Anytime);
+            },
+        ]);
+        let call: Program<M> = Program::conditional(
+            &[
+                fetched,
+                |c, _, _| c.move_reg_immediate(Z),
+                |c, _, _| {
+                    c.move_reg_immediate(W);
+                    _ = c.satisfying_condition(Anytime);
+                },
+                |c, _, _| c.push_from(PC.split()[0]),
+                |c, _, _| {
+                    c.push_from(PC.split()[1]);
+                    c.load_reg16_from_reg16(PC, WZ);
+                },
+            ],
+            2,
+        );
+        let (op, dst, src) = decompose(opcode);
         match op {
             0 => match (dst, src) {
-                (0, 0) => self.no_op(),
-                (_, 0) => self.no_op(), // <= unspecified
-                (0, 7) => self.alu_with_reg(RotateLeft, Acc),
-                (1, 7) => self.alu_with_reg(RotateRight, Acc),
-                (2, 7) => self.alu_with_reg(RotateLeftThroughCarry, Acc),
-                (3, 7) => self.alu_with_reg(RotateRightThroughCarry, Acc),
-                (4, 2) => self.store_reg16_direct(HL),
-                (4, 7) => self.alu_with_reg(DecimalAdjust, Acc),
-                (5, 2) => self.move_reg16_direct(HL),
-                (5, 7) => self.alu_with_reg(ComplementAcc, Acc),
-                (6, 2) => self.store_reg_direct(Acc),
-                (6, 4) => self.alu_with_mem_to_mem(Increment),
-                (6, 5) => self.alu_with_mem_to_mem(Decrement),
-                (6, 6) => self.store_hl_immediate(),
-                (6, 7) => self.flag_complement(StatusFlag::Carry),
-                (7, 2) => self.move_reg_direct(Acc),
-                (7, 7) => self.flag_set(StatusFlag::Carry),
-                (dst, 1) if dst % 2 == 0 => {
-                    self.move_reg16_immediate(Self::reg16_code_from_bits(dst / 2))
-                }
+                (0, 0) => one(|c, _, _| c.no_op()),
+                (4, 0) => one(|c, _, _| match c.variant {
+                    I8080Variant::I8085 => c.read_interrupt_mask(),
+                    I8080Variant::I8080 => c.no_op(), // <= unspecified
+                }),
+                (6, 0) => one(|c, _, _| match c.variant {
+                    I8080Variant::I8085 => c.set_interrupt_mask(),
+                    I8080Variant::I8080 => c.no_op(), // <= unspecified
+                }),
+                (_, 0) => one(|c, _, _| c.no_op()), // <= unspecified
+                (0, 7) => one(|c, _, _| c.alu_with_reg(RotateLeft, Acc)),
+                (1, 7) => one(|c, _, _| c.alu_with_reg(RotateRight, Acc)),
+                (2, 7) => one(|c, _, _| c.alu_with_reg(RotateLeftThroughCarry, Acc)),
+                (3, 7) => one(|c, _, _| c.alu_with_reg(RotateRightThroughCarry, Acc)),
+                (4, 2) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.move_reg_immediate(Z),
+                    |c, _, _| c.move_reg_immediate(W),
+                    |c, _, _| c.store_at_wz(L),
+                    |c, _, _| {
+                        c.reg16_increment(WZ);
+                        c.store_at_wz(H);
+                    },
+                ]),
+                (4, 7) => one(|c, _, _| c.alu_with_reg(DecimalAdjust, Acc)),
+                (5, 2) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.move_reg_immediate(Z),
+                    |c, _, _| c.move_reg_immediate(W),
+                    |c, _, _| c.fetch_at_wz(L),
+                    |c, _, _| {
+                        c.reg16_increment(WZ);
+                        c.fetch_at_wz(H);
+                    },
+                ]),
+                (5, 7) => one(|c, _, _| c.alu_with_reg(ComplementAcc, Acc)),
+                (6, 2) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.move_reg_immediate(Z),
+                    |c, _, _| c.move_reg_immediate(W),
+                    |c, _, _| c.store_at_wz(Acc),
+                ]),
+                (6, 4) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.fetch_hl_to_tmp(),
+                    |c, _, _| c.alu_to_hl_mem(Increment),
+                ]),
+                (6, 5) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.fetch_hl_to_tmp(),
+                    |c, _, _| c.alu_to_hl_mem(Decrement),
+                ]),
+                (6, 6) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.fetch_instruction(),
+                    |c, _, _| c.store_hl(),
+                ]),
+                (6, 7) => one(|c, _, _| c.flag_complement(StatusFlag::Carry)),
+                (7, 2) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.move_reg_immediate(Z),
+                    |c, _, _| c.move_reg_immediate(W),
+                    |c, _, _| c.fetch_at_wz(Acc),
+                ]),
+                (7, 7) => one(|c, _, _| c.flag_set(StatusFlag::Carry)),
+                (dst, 1) if dst % 2 == 0 => Program::new(&[
+                    fetched,
+                    |c, dst, _| {
+                        c.move_reg_immediate(Self::reg16_code_from_bits(dst / 2).split()[1])
+                    },
+                    |c, dst, _| {
+                        c.move_reg_immediate(Self::reg16_code_from_bits(dst / 2).split()[0])
+                    },
+                ]),
                 (dst, 2) if dst % 2 == 0 => {
-                    self.move_indirect(Acc, Self::reg16_code_from_bits(dst / 2))
+                    two(|c, dst, _| c.store_indirect(Acc, Self::reg16_code_from_bits(dst / 2)))
                 }
                 (dst, 2) if dst % 2 == 1 => {
-                    self.move_indirect(Acc, Self::reg16_code_from_bits(dst / 2))
+                    two(|c, dst, _| c.move_indirect(Acc, Self::reg16_code_from_bits(dst / 2)))
                 }
                 (dst, 3) if dst % 2 == 0 => {
-                    self.reg16_increment(Self::reg16_code_from_bits(dst / 2));
+                    one(|c, dst, _| c.reg16_increment(Self::reg16_code_from_bits(dst / 2)))
                 }
                 (dst, 3) if dst % 2 == 1 => {
-                    self.reg16_increment(Self::reg16_code_from_bits(dst / 2));
+                    one(|c, dst, _| c.reg16_decrement(Self::reg16_code_from_bits(dst / 2)))
                 }
-                (dst, 4) => self.alu_with_reg_to_reg(Increment, Self::reg_code_from_bits(dst)),
-                (dst, 5) => self.alu_with_reg_to_reg(Decrement, Self::reg_code_from_bits(dst)),
-                (dst, 6) => self.move_reg_immediate(Self::reg_code_from_bits(dst)),
-                (rhs, 1) if rhs % 2 == 1 => {
-                    let hl = self.code_reg16_as_u16(HL);
-                    let rp = self.code_reg16_as_u16(Self::reg16_code_from_bits(rhs / 2));
-                    let (res, carry) = hl.overflowing_add(rp);
-                    if carry {
-                        self.code_reg_mut(Flag)
-                            .reg
-                            .masked(Self::flag_decode(StatusFlag::Carry))
-                            .load(!0)
-                    } else {
-                        self.code_reg_mut(Flag)
-                            .reg
-                            .masked(Self::flag_decode(StatusFlag::Carry))
-                            .load(0)
-                    }
-                    HL.split()
-                        .into_iter()
-                        .zip(res.to_be_bytes())
-                        .for_each(|(c, x)| self.code_reg_mut(c).reg.load(x));
+                (_, 4) => {
+                    one(|c, dst, _| c.alu_with_reg_to_reg(Increment, Self::reg_code_from_bits(dst)))
+                }
+                (_, 5) => {
+                    one(|c, dst, _| c.alu_with_reg_to_reg(Decrement, Self::reg_code_from_bits(dst)))
                 }
-                _ => self.no_op(),
+                (_, 6) => two(|c, dst, _| c.move_reg_immediate(Self::reg_code_from_bits(dst))),
+                (_, 1) => one(|c, dst, _| c.dad(Self::reg16_code_from_bits(dst / 2))),
+                _ => unreachable!(),
             },
             1 => match (dst, src) {
-                (6, 6) => self.halt(),
-                (dst, 6) => self.move_hl_mem_to_reg(Self::reg_code_from_bits(dst)),
-                (6, src) => self.store_reg_to_hl_mem(Self::reg_code_from_bits(src)),
-                (dst, src) => self
-                    .move_reg_to_reg(Self::reg_code_from_bits(dst), Self::reg_code_from_bits(src)),
+                (6, 6) => two(|c, _, _| c.halt()),
+                (_, 6) => two(|c, dst, _| c.move_hl_mem_to_reg(Self::reg_code_from_bits(dst))),
+                (6, _) => two(|c, _, src| c.store_reg_to_hl_mem(Self::reg_code_from_bits(src))),
+                (_, _) => one(|c, dst, src| {
+                    c.move_reg_to_reg(Self::reg_code_from_bits(dst), Self::reg_code_from_bits(src))
+                }),
             },
             2 => match (dst, src) {
-                (0, 6) => self.alu_with_mem(Add),
-                (0, src) => self.alu_with_reg(Add, Self::reg_code_from_bits(src)),
-                (1, 6) => self.alu_with_mem(AddCarried),
-                (1, src) => self.alu_with_reg(AddCarried, Self::reg_code_from_bits(src)),
-                (2, 6) => self.alu_with_mem(Sub),
-                (2, src) => self.alu_with_reg(Sub, Self::reg_code_from_bits(src)),
-                (3, 6) => self.alu_with_mem(SubBorrowed),
-                (3, src) => self.alu_with_reg(SubBorrowed, Self::reg_code_from_bits(src)),
-                (4, 6) => self.alu_with_mem(LogicAnd),
-                (4, src) => self.alu_with_reg(LogicAnd, Self::reg_code_from_bits(src)),
-                (5, 6) => self.alu_with_mem(LogicXor),
-                (5, src) => self.alu_with_reg(LogicXor, Self::reg_code_from_bits(src)),
-                (6, 6) => self.alu_with_mem(LogicOr),
-                (6, src) => self.alu_with_reg(LogicOr, Self::reg_code_from_bits(src)),
-                (7, 6) => self.cmp_with_mem(),
-                (7, src) => self.cmp_with_reg(Self::reg_code_from_bits(src)),
+                (0, 6) => two(|c, _, _| c.alu_with_mem(Add)),
+                (0, _) => one(|c, _, src| c.alu_with_reg(Add, Self::reg_code_from_bits(src))),
+                (1, 6) => two(|c, _, _| c.alu_with_mem(AddCarried)),
+                (1, _) => {
+                    one(|c, _, src| c.alu_with_reg(AddCarried, Self::reg_code_from_bits(src)))
+                }
+                (2, 6) => two(|c, _, _| c.alu_with_mem(Sub)),
+                (2, _) => one(|c, _, src| c.alu_with_reg(Sub, Self::reg_code_from_bits(src))),
+                (3, 6) => two(|c, _, _| c.alu_with_mem(SubBorrowed)),
+                (3, _) => {
+                    one(|c, _, src| c.alu_with_reg(SubBorrowed, Self::reg_code_from_bits(src)))
+                }
+                (4, 6) => two(|c, _, _| c.alu_with_mem(LogicAnd)),
+                (4, _) => one(|c, _, src| c.alu_with_reg(LogicAnd, Self::reg_code_from_bits(src))),
+                (5, 6) => two(|c, _, _| c.alu_with_mem(LogicXor)),
+                (5, _) => one(|c, _, src| c.alu_with_reg(LogicXor, Self::reg_code_from_bits(src))),
+                (6, 6) => two(|c, _, _| c.alu_with_mem(LogicOr)),
+                (6, _) => one(|c, _, src| c.alu_with_reg(LogicOr, Self::reg_code_from_bits(src))),
+                (7, 6) => two(|c, _, _| c.cmp_with_mem()),
+                (7, _) => one(|c, _, src| c.cmp_with_reg(Self::reg_code_from_bits(src))),
                 _ => unreachable!(),
             },
             3 => match (dst, src) {
-                (1, 1) => self.ret(Anytime),
-                (2, 1) => self.ret(Anytime), // <= unspecified
-                (4, 1) => self.pop_reg16(PSW),
-                (5, 1) => self.load_reg16_from_reg16(PC, HL),
-                (7, 1) => self.load_reg16_from_reg16(SP, HL),
-                (0, 3) => self.jump_immediate(Anytime),
-                (1, 3) => self.jump_immediate(Anytime), // <= unspecified
-                (2, 3) => self.output(),
-                (3, 3) => self.input(),
-                (4, 3) => self.exchange_stack_top_with_hl(),
-                (5, 3) => self.exchange16(HL, DE),
-                (6, 3) => self.disable_interrupt(),
-                (7, 3) => self.enable_interrupt(),
-                (1, 5) => self.call_immediate(Anytime),
-                (3, 5) => self.call_immediate(Anytime), // <= unspecified
-                (4, 5) => self.push_reg16(PSW),
-                (5, 5) => self.call_immediate(Anytime), // <= unspecified
-                (7, 5) => self.call_immediate(Anytime), // <= unspecified
-                (0, 6) => self.alu_with_immediate(Add),
-                (1, 6) => self.alu_with_immediate(AddCarried),
-                (2, 6) => self.alu_with_immediate(Sub),
-                (3, 6) => self.alu_with_immediate(SubBorrowed),
-                (4, 6) => self.alu_with_immediate(LogicAnd),
-                (5, 6) => self.alu_with_immediate(LogicXor),
-                (6, 6) => self.alu_with_immediate(LogicOr),
-                (7, 6) => self.cmp_with_immediate(),
-                (cond, 0) => self.ret(Self::condition_code_from_bits(cond)),
-                (cond, 2) => self.jump_immediate(Self::condition_code_from_bits(cond)),
-                (cond, 4) => self.call_immediate(Self::condition_code_from_bits(cond)),
-                (dst, 1) if dst % 2 == 0 => self.pop_reg16(Self::reg16_code_from_bits(dst / 2)),
-                (dst, 5) if dst % 2 == 0 => self.push_reg16(Self::reg16_code_from_bits(dst / 2)),
-                (n, 7) => self.restart(n),
-                _ => self.no_op(),
+                (1, 1) => ret,
+                (3, 1) => ret, // <= unspecified
+                (6, 1) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.pop_to(PSW.split()[1]),
+                    |c, _, _| c.pop_high(PSW),
+                ]),
+                (5, 1) => one(|c, _, _| c.load_reg16_from_reg16(PC, HL)),
+                (7, 1) => one(|c, _, _| c.load_reg16_from_reg16(SP, HL)),
+                (0, 3) => jump,
+                (1, 3) => jump, // <= unspecified
+                (2, 3) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.fetch_instruction(),
+                    |c, _, _| c.output_cycle(),
+                ]),
+                (3, 3) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.fetch_instruction(),
+                    |c, _, _| c.input_cycle(),
+                ]),
+                (4, 3) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.fetch_stack_to(Z),
+                    |c, _, _| {
+                        c.store_stack_from(L);
+                        c.reg16_increment(SP);
+                    },
+                    |c, _, _| c.fetch_stack_to(W),
+                    |c, _, _| {
+                        c.store_stack_from(H);
+                        c.reg16_decrement(SP);
+                        c.load_reg16_from_reg16(HL, WZ);
+                    },
+                ]),
+                (5, 3) => one(|c, _, _| c.exchange16(HL, DE)),
+                (6, 3) => one(|c, _, _| c.disable_interrupt()),
+                (7, 3) => one(|c, _, _| c.enable_interrupt()),
+                (1, 5) => call,
+                (3, 5) => call, // <= unspecified
+                (6, 5) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.push_from(PSW.split()[0]),
+                    |c, _, _| c.push_from(PSW.split()[1]),
+                ]),
+                (5, 5) => call, // <= unspecified
+                (7, 5) => call, // <= unspecified
+                (0, 6) => two(|c, _, _| c.alu_with_immediate(Add)),
+                (1, 6) => two(|c, _, _| c.alu_with_immediate(AddCarried)),
+                (2, 6) => two(|c, _, _| c.alu_with_immediate(Sub)),
+                (3, 6) => two(|c, _, _| c.alu_with_immediate(SubBorrowed)),
+                (4, 6) => two(|c, _, _| c.alu_with_immediate(LogicAnd)),
+                (5, 6) => two(|c, _, _| c.alu_with_immediate(LogicXor)),
+                (6, 6) => two(|c, _, _| c.alu_with_immediate(LogicOr)),
+                (7, 6) => two(|c, _, _| c.cmp_with_immediate()),
+                (_, 0) => Program::conditional(
+                    &[
+                        |c, cond, _| {
+                            _ = c.satisfying_condition(Self::condition_code_from_bits(cond))
+                        },
+                        ret.steps[1],
+                        ret.steps[2],
+                    ],
+                    0,
+                ),
+                (_, 2) => Program::new(&[fetched, jump.steps[1], |c, cond, _| {
+                    c.move_reg_immediate(W);
+                    c.jump_to_wz(Self::condition_code_from_bits(cond));
+                }]),
+                (_, 4) => Program::conditional(
+                    &[
+                        fetched,
+                        call.steps[1],
+                        |c, cond, _| {
+                            c.move_reg_immediate(W);
+                            _ = c.satisfying_condition(Self::condition_code_from_bits(cond));
+                        },
+                        call.steps[3],
+                        call.steps[4],
+                    ],
+                    2,
+                ),
+                (dst, 1) if dst % 2 == 0 => Program::new(&[
+                    fetched,
+                    |c, dst, _| c.pop_to(Self::reg16_code_from_bits(dst / 2).split()[1]),
+                    |c, dst, _| c.pop_high(Self::reg16_code_from_bits(dst / 2)),
+                ]),
+                (dst, 5) if dst % 2 == 0 => Program::new(&[
+                    fetched,
+                    |c, dst, _| c.push_from(Self::reg16_code_from_bits(dst / 2).split()[0]),
+                    |c, dst, _| c.push_from(Self::reg16_code_from_bits(dst / 2).split()[1]),
+                ]),
+                (_, 7) => Program::new(&[
+                    fetched,
+                    |c, _, _| c.push_from(PC.split()[0]),
+                    |c, n, _| {
+                        c.push_from(PC.split()[1]);
+                        c.load_reg16(PC, n as u16 * 8);
+                    },
+                ]),
+                _ => unreachable!(),
             },
             _ => unreachable!(),
         }
     }
 
-    pub fn run(&mut self) {
-        self.halted = false;
-        while !self.halted {
-            self.execute();
+    /// the steps of `opcode` from the dispatch table, built on first use.
+    fn program_of(&mut self, opcode: u8) -> Program<M> {
+        let dispatch = self.dispatch.get_or_insert_with(|| {
+            Dispatch(Box::new(std::array::from_fn(|opcode| {
+                Self::program(opcode as u8)
+            })))
+        });
+        dispatch.0[opcode as usize]
+    }
+
+    /// start the next instruction, or the interrupt or BDOS call served in place of it,
+    /// by its opcode fetch.
+    fn begin(&mut self) {
+        use I8080RegisterCode::*;
+        self.input_pending = None;
+        if self.history.as_ref().is_some_and(|h| h.depth > 0) {
+            let state = self.snapshot();
+            let history = self.history.as_mut().unwrap();
+            if history.entries.len() == history.depth {
+                history.entries.pop_front();
+            }
+            history.entries.push_back((state, vec![]));
+        }
+        self.branch_taken = false;
+        // a staged instruction runs as given, leaving interrupts and BDOS calls pending.
+        let staged = !self.staged.is_empty();
+        if !staged && (self.begin_hardware_rst() || (self.bdos && self.begin_bdos_call())) {
+            return;
         }
+        self.instructions_executed += 1;
+        let state = self.trace.is_some().then(|| self.snapshot());
+        self.cycle_type = Some(I8080CycleType::OpcodeFetch);
+        self.fetch_instruction();
+        self.code_reg_mut(Inst).load_from_data();
+        let inst = self.code_reg_mut(Inst).reg.read();
+        if let (Some(Trace(hook)), Some(state)) = (&mut self.trace, state) {
+            hook(TraceEvent {
+                pc: state.pc,
+                opcode: inst,
+                state,
+            });
+        }
+        if let Some(histogram) = &mut self.opcode_histogram {
+            histogram[inst as usize] += 1;
+        }
+        let (_, dst, src) = decompose(inst);
+        self.in_flight = Some(InFlight {
+            program: self.program_of(inst),
+            next: 0,
+            opcode: inst,
+            dst,
+            src,
+        });
     }
-}
 
-impl<M: Memory<Data = u8, Address = u16>> ProcMemory for I8080Console<M> {
-    fn store(&mut self) {
+    /// run the next step of the instruction in flight. returns T-states the instruction
+    /// took if that was its last step.
+    fn advance(&mut self) -> Option<u8> {
+        use I8080RegisterCode::*;
+        let mut in_flight = self.in_flight.take()?;
+        let step = in_flight.next;
+        (in_flight.program.steps[step as usize])(self, in_flight.dst, in_flight.src);
+        in_flight.next += 1;
+        let cut = in_flight.program.condition == Some(step) && !self.branch_taken;
+        if in_flight.next < in_flight.program.len && !cut {
+            self.in_flight = Some(in_flight);
+            return None;
+        }
+        if self.clear_scratch {
+            self.code_reg_mut(W).reg.load(0);
+            self.code_reg_mut(Z).reg.load(0);
+        }
+        let states = self.timing.states(in_flight.opcode, self.branch_taken);
+        self.spend(states);
+        Some(states)
+    }
+
+    /// run the rest of the instruction in flight if any, and returns T-states it took.
+    fn finish(&mut self) -> Option<u8> {
+        loop {
+            self.in_flight.as_ref()?;
+            if let Some(states) = self.advance() {
+                return Some(states);
+            }
+        }
+    }
+
+    /// execute an instruction, and returns T-states it took. one left partway by
+    /// `step_cycle()` is finished instead.
+    pub fn execute(&mut self) -> u8 {
+        if let Some(states) = self.finish() {
+            return states;
+        }
+        self.begin();
+        self.finish().expect("an instruction was just begun")
+    }
+
+    /// execute `opcode` with its `operands` as if fetched from PC, without touching memory
+    /// there. PC still advances past them. other memory accesses go to memory as usual.
+    /// missing operands read as 0, and extra ones are ignored. a pending interrupt or BDOS
+    /// call waits for the next instruction.
+    pub fn execute_opcode(&mut self, opcode: u8, operands: &[u8]) -> u8 {
+        self.finish();
+        let (_, len) = crate::decode::decode(&[opcode]).expect("an opcode is given");
+        self.staged = std::iter::once(opcode)
+            .chain(operands.iter().copied().chain(std::iter::repeat(0)))
+            .take(len)
+            .collect();
+        let states = self.execute();
+        self.staged.clear();
+        states
+    }
+
+    /// execute `inst` as if fetched from PC, like [`Self::execute_opcode`] with its bytes.
+    pub fn execute_decoded(&mut self, inst: decode::Instruction) -> u8 {
+        let bytes = inst.encode();
+        self.execute_opcode(bytes[0], &bytes[1..])
+    }
+
+    /// W and Z are scratch of multi-byte instructions, and persist between instructions
+    /// as on the real chip although no instruction should read them before writing.
+    /// enable this to clear them at every instruction boundary, which makes an instruction
+    /// depending on stale scratch misbehave visibly.
+    pub fn set_scratch_clearing(&mut self, clear: bool) {
+        self.clear_scratch = clear;
+    }
+
+    /// fill `len` bytes from `start` with `value` in memory, bypassing the processor.
+    pub fn memset(&mut self, start: u16, value: u8, len: usize) {
         self.memory
             .lock()
             .unwrap()
-            .write(self.address_bus.get(), self.data_bus.get())
+            .write_range(start, &vec![value; len]);
     }
 
-    fn fetch(&mut self) {
-        self.data_bus
-            .set(self.memory.lock().unwrap().read(self.address_bus.get()));
+    /// whether the `len` bytes from `a` and from `b` in memory are equal.
+    pub fn memcmp(&self, a: u16, b: u16, len: usize) -> bool {
+        let memory = self.memory.lock().unwrap();
+        memory.read_range(a, len) == memory.read_range(b, len)
     }
-}
 
-#[derive(Debug, Default)]
-pub struct I8080AllRAM {
-    proc: I8080Console<RamB8A16>,
-}
+    /// undo the latest instruction recorded since `enable_history()`. false if none is left.
+    pub fn step_back(&mut self) -> bool {
+        let Some((state, writes)) = self.history.as_mut().and_then(|h| h.entries.pop_back()) else {
+            return false;
+        };
+        let mut memory = self.memory.lock().unwrap();
+        for &(address, data) in writes.iter().rev() {
+            memory.poke(address, data);
+        }
+        drop(memory);
+        self.restore(&state);
+        self.in_flight = None;
+        true
+    }
 
-impl I8080AllRAM {
-    pub fn flash(&mut self, data: &[u8]) {
-        self.proc.memory.lock().unwrap().flash(data, 0);
+    /// opcode of the instruction executed last, held in the instruction register.
+    pub fn current_opcode(&self) -> u8 {
+        self.code_reg_as_u8(I8080RegisterCode::Inst)
     }
-    pub fn run(&mut self) {
-        self.proc.run();
+
+    /// make `IN port` read from `handler` instead of the port latch.
+    pub fn set_input_port(&mut self, port: u8, handler: Box<dyn FnMut() -> u8>) {
+        self.io_handlers.input.insert(port, handler);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::memory::RamB8A16;
-    use crate::processor::i8080::I8080Console;
-    use std::sync::{Arc, Mutex};
-    #[test]
-    fn movement() {
-        use crate::memory::RamB8A16;
-        use I8080RegisterCode::*;
-        use I8080RegisterCode16::*;
-        let mut row_memory = RamB8A16::default();
-        row_memory.flash(&(0..=255u8).cycle().take(65535).collect::<Vec<_>>(), 0);
-        let memory = Arc::new(Mutex::new(row_memory));
+    /// make `IN port` read from `source`, retrying the `IN` until it has a byte.
+    /// attached peripherals still answer first.
+    pub fn set_input_source(&mut self, port: u8, source: Box<dyn InputSource>) {
+        self.input_sources.insert(port, source);
+    }
+
+    /// the port the last instruction waited on, if it was an `IN` finding no input.
+    pub fn input_pending(&self) -> Option<u8> {
+        self.input_pending
+    }
+
+    /// make `OUT port` also pass the accumulator to `handler`.
+    pub fn set_output_port(&mut self, port: u8, handler: Box<dyn FnMut(u8)>) {
+        self.io_handlers.output.insert(port, handler);
+    }
+
+    /// the values last written to each output port, which input ports read back.
+    pub fn io_ports(&self) -> &[u8; 256] {
+        &self.io_space.0
+    }
+
+    fn next_opcode(&self) -> u8 {
+        use I8080RegisterCode16::PC;
+        self.memory.lock().unwrap().read(self.code_reg16_as_u16(PC))
+    }
+
+    /// whether the instruction at PC is a call, to be stepped over.
+    pub fn next_is_call(&self) -> bool {
+        Self::is_call_opcode(self.next_opcode())
+    }
+
+    /// whether the instruction at PC is a jump or a return.
+    pub fn next_is_branch(&self) -> bool {
+        Self::is_branch_opcode(self.next_opcode())
+    }
+
+    /// advance exactly one machine cycle (one bus transaction), running the part of the
+    /// instruction done in it. registers and memory are left as that cycle leaves them,
+    /// and `execute()` finishes an instruction left partway. an interrupt or BDOS call
+    /// served in place of an instruction is stepped through alike.
+    /// while halted, every call reports another halt acknowledge cycle.
+    pub fn step_cycle(&mut self) -> CycleInfo {
+        if self.in_flight.is_none() && self.halted.get() {
+            self.halt();
+        } else {
+            self.last_cycle = None;
+            while self.last_cycle.is_none() {
+                if self.in_flight.is_none() {
+                    self.begin();
+                }
+                self.advance();
+            }
+        }
+        self.last_cycle.expect("a bus cycle just ran")
+    }
+
+    /// execute one instruction unless halted. a halted processor only checks for
+    /// interrupts, so stepping it again is cheap and leaves PC as it is. the instruction
+    /// after `HLT` therefore never runs from here before an interrupt or `reset()`; call
+    /// [`Self::execute`] to run it regardless of the halted flag.
+    pub fn step(&mut self) -> StepStatus {
+        if !self.halted.get() {
+            return StepStatus::Executed(self.execute());
+        }
+        if let Some(states) = self.accept_hardware_rst() {
+            return StepStatus::Executed(states);
+        }
+        if self.interrupt_enabled.get() {
+            StepStatus::Halted
+        } else {
+            StepStatus::HaltedForever
+        }
+    }
+
+    /// run until halted, a breakpoint, or stopped via the handle from [`Self::stop_handle`].
+    pub fn run(&mut self) {
+        self.run_until_break();
+    }
+
+    /// run until halted, or until PC reaches a breakpoint. the instruction at PC on entry
+    /// runs regardless, so that calling it again resumes from a breakpoint.
+    pub fn run_until_break(&mut self) -> StopReason {
+        use I8080RegisterCode16::PC;
+        self.halted.set(false);
+        let mut first = true;
+        while !self.halted.get() {
+            if self.stop_request.swap(false, Ordering::Relaxed) {
+                return StopReason::Stopped;
+            }
+            let pc = self.code_reg16_as_u16(PC);
+            if !first && self.breakpoints.contains(&pc) {
+                return StopReason::Breakpoint(pc);
+            }
+            first = false;
+            self.execute();
+            if let Some(port) = self.input_pending {
+                return StopReason::InputPending(port);
+            }
+        }
+        StopReason::Halted
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    /// run at most `max` instructions, or until halted or caught by the watchdog.
+    pub fn run_limited(&mut self, max: u64) -> RunResult {
+        use I8080RegisterCode16::PC;
+        self.halted.set(false);
+        let mut unchanged = 0;
+        // states since the last write, the latest `limit` of them.
+        let mut recent = VecDeque::from([self.snapshot()]);
+        for _ in 0..max {
+            let pc = self.code_reg16_as_u16(PC);
+            let write_cycles = self.write_cycles;
+            self.execute();
+            if self.halted.get() {
+                return RunResult::Halted;
+            }
+            let Some(limit) = self.stuck_watchdog else {
+                continue;
+            };
+            let state = self.snapshot();
+            if self.write_cycles != write_cycles {
+                recent.clear();
+            }
+            if state.pc == pc || recent.contains(&state) {
+                unchanged += 1;
+            } else {
+                unchanged = 0;
+            }
+            if recent.len() >= limit as usize {
+                recent.pop_front();
+            }
+            recent.push_back(state);
+            if unchanged >= limit {
+                return RunResult::Stuck(pc);
+            }
+        }
+        RunResult::LimitReached
+    }
+
+    /// run at most `max` instructions until the current subroutine returns, i.e. until
+    /// a return pops SP above where it was on entry and PC to the return address found on
+    /// top of the stack then. calls made meanwhile run through. SP may wrap around.
+    pub fn step_out(&mut self, max: u64) -> RunResult {
+        use I8080RegisterCode16::{PC, SP};
+        self.halted.set(false);
+        let entry = self.code_reg16_as_u16(SP);
+        let memory = self.memory.lock().unwrap();
+        let return_address =
+            u16::from_le_bytes([memory.read(entry), memory.read(entry.wrapping_add(1))]);
+        drop(memory);
+        for _ in 0..max {
+            self.execute();
+            if self.halted.get() {
+                return RunResult::Halted;
+            }
+            let popped = self.code_reg16_as_u16(SP).wrapping_sub(entry) as i16 > 0;
+            if Self::is_return_opcode(self.current_opcode())
+                && popped
+                && self.code_reg16_as_u16(PC) == return_address
+            {
+                return RunResult::Returned;
+            }
+        }
+        RunResult::LimitReached
+    }
+
+    /// make `run_limited()` report [`RunResult::Stuck`] once `limit` consecutive instructions
+    /// make no progress: each leaves PC as it was, or returns to a state among the last
+    /// `limit` ones with nothing written to memory or a port since. `None`, the default,
+    /// disables it.
+    pub fn set_stuck_watchdog(&mut self, limit: Option<u32>) {
+        self.stuck_watchdog = limit;
+    }
+
+    /// set it true to make `run()` return before the next instruction, e.g. from another thread.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop_request)
+    }
+}
+
+impl<M: Memory<Data = u8, Address = u16>> ProcMemory for I8080Console<M> {
+    fn store(&mut self) {
+        let mut memory = self.memory.lock().unwrap();
+        let address = self.address_bus.get();
+        if let Some((_, writes)) = self.history.as_mut().and_then(|h| h.entries.back_mut()) {
+            writes.extend(memory.peek(address).map(|old| (address, old)));
+        }
+        memory.write(address, self.regs.bus.get());
+        drop(memory);
+        self.record_cycle(I8080CycleType::MemoryWrite);
+    }
+
+    fn fetch(&mut self) {
+        self.regs
+            .bus
+            .set(self.memory.lock().unwrap().read(self.address_bus.get()));
+        self.record_cycle(I8080CycleType::MemoryRead);
+    }
+}
+
+impl<M> ProcDataRegisters<I8080RegisterCode> for I8080Console<M> {
+    fn data_reg_read(&mut self, code: &I8080RegisterCode) {
+        self.code_reg_mut(*code).read_to_data()
+    }
+
+    fn data_reg_load(&mut self, code: &I8080RegisterCode) {
+        self.code_reg_mut(*code).load_from_data()
+    }
+}
+
+impl<M> ProcAddressingRegisters<I8080RegisterCode16> for I8080Console<M> {
+    fn addressing_reg_read(&mut self, code: &I8080RegisterCode16) {
+        self.code_reg16_read_to_address(*code)
+    }
+}
+
+impl I8080Console<RamB8A16> {
+    pub fn flash(&mut self, data: &[u8]) {
+        self.memory.lock().unwrap().flash(data, 0);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct I8080AllRAM {
+    proc: I8080Console<RamB8A16>,
+}
+
+impl I8080AllRAM {
+    pub fn flash(&mut self, data: &[u8]) {
+        self.proc.memory.lock().unwrap().flash(data, 0);
+    }
+    pub fn run(&mut self) {
+        self.proc.run();
+    }
+}
+
+/// set up an [`I8080Console`] over flat RAM, e.g. `I8080ConsoleBuilder::new().hl(0x1234).build()`.
+#[derive(Debug, Default)]
+pub struct I8080ConsoleBuilder {
+    console: I8080Console,
+}
+
+impl I8080ConsoleBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn acc(mut self, value: u8) -> Self {
+        self.console
+            .code_reg_mut(I8080RegisterCode::Acc)
+            .reg
+            .load(value);
+        self
+    }
+    pub fn bc(mut self, value: u16) -> Self {
+        self.console.set_reg16(I8080RegisterCode16::BC, value);
+        self
+    }
+    pub fn de(mut self, value: u16) -> Self {
+        self.console.set_reg16(I8080RegisterCode16::DE, value);
+        self
+    }
+    pub fn hl(mut self, value: u16) -> Self {
+        self.console.set_reg16(I8080RegisterCode16::HL, value);
+        self
+    }
+    pub fn sp(mut self, value: u16) -> Self {
+        self.console.set_reg16(I8080RegisterCode16::SP, value);
+        self
+    }
+    pub fn pc(mut self, value: u16) -> Self {
+        self.console.set_reg16(I8080RegisterCode16::PC, value);
+        self
+    }
+    pub fn flags(mut self, flags: EnumSet<StatusFlag>) -> Self {
+        let flags = I8080Console::<RamB8A16>::flag_scramble(flags);
+        self.console
+            .code_reg_mut(I8080RegisterCode::Flag)
+            .reg
+            .load(flags);
+        self
+    }
+    /// flash `program` at address 0.
+    pub fn program(mut self, program: &[u8]) -> Self {
+        self.console.flash(program);
+        self
+    }
+    pub fn build(self) -> I8080Console {
+        self.console
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::RamB8A16;
+    use crate::processor::i8080::I8080Console;
+    use std::sync::{Arc, Mutex};
+    #[test]
+    fn movement() {
+        use crate::memory::RamB8A16;
+        use I8080RegisterCode::*;
+        use I8080RegisterCode16::*;
+        let mut row_memory = RamB8A16::default();
+        row_memory.flash(&(0..=255u8).cycle().take(65535).collect::<Vec<_>>(), 0);
+        let memory = Arc::new(Mutex::new(row_memory));
         let mut c = I8080Console {
             memory: Arc::clone(&memory),
             ..Default::default()
         };
         c.move_reg_immediate(B);
         c.move_reg_to_reg(C, B);
-        assert_eq!(c.regs.get(&C).unwrap().reg.read(), 0);
+        assert_eq!(c.code_reg_as_u8(C), 0);
         c.move_reg_immediate(D);
         c.move_reg_to_reg(E, D);
-        assert_eq!(c.regs.get(&E).unwrap().reg.read(), 1);
+        assert_eq!(c.code_reg_as_u8(E), 1);
         // load (0x0302) == 2
         c.move_reg_direct(Acc);
-        assert_eq!(c.regs.get(&Acc).unwrap().reg.read(), 2);
+        assert_eq!(c.code_reg_as_u8(Acc), 2);
         // loadx (0x0504) == 0x0504
         c.move_reg16_direct(HL);
         assert_eq!(c.code_reg16_as_u16(HL), 0x0504);
@@ -972,4 +2352,1808 @@ mod tests {
         println!("{:?}", c);
         println!("{}", c.code_reg16_as_u16(PC));
     }
+
+    #[test]
+    fn rotate_matrix() {
+        use I8080RegisterCode::{Acc, Flag};
+        // RLC, RRC, RAL, RAR
+        for opcode in [0x07, 0x0f, 0x17, 0x1f] {
+            for acc in [0x00u8, 0x01, 0x7f, 0x80, 0x81, 0xfe, 0xff, 0x55] {
+                for carry in [false, true] {
+                    let (res, carry_out) = match opcode {
+                        0x07 => (acc.rotate_left(1), acc & 0x80 != 0),
+                        0x0f => (acc.rotate_right(1), acc & 0x01 != 0),
+                        0x17 => (acc << 1 | carry as u8, acc & 0x80 != 0),
+                        _ => (acc >> 1 | (carry as u8) << 7, acc & 0x01 != 0),
+                    };
+                    let mut c = I8080Console::default();
+                    c.flash(&[opcode]);
+                    c.code_reg_mut(Acc).reg.load(acc);
+                    // Z, S, P and AC are left as they are
+                    c.code_reg_mut(Flag).reg.load(0xd6 | carry as u8);
+                    c.execute();
+                    assert_eq!(c.code_reg_as_u8(Acc), res, "{opcode:02x} {acc:02x} {carry}");
+                    assert_eq!(c.code_reg_as_u8(Flag), 0xd6 | carry_out as u8);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotate_manual_examples() {
+        use I8080RegisterCode::Acc;
+        // examples of the 8080 programmer's manual
+        for (opcode, acc, carry, expected) in [
+            (0x07, 0xf2, false, (0xe5, true)),
+            (0x0f, 0xf2, false, (0x79, false)),
+            (0x17, 0xb5, false, (0x6a, true)),
+            (0x1f, 0x6a, true, (0xb5, false)),
+        ] {
+            let mut c = I8080Console::default();
+            c.flash(&[opcode]);
+            c.code_reg_mut(Acc).reg.load(acc);
+            if carry {
+                c.flag_set(StatusFlag::Carry);
+            }
+            c.execute();
+            let carry_out = c.flag_status().contains(StatusFlag::Carry);
+            assert_eq!((c.code_reg_as_u8(Acc), carry_out), expected);
+        }
+    }
+
+    #[test]
+    fn next_control_transfer() {
+        // CALL, CNZ, RST 1, JMP, JZ, PCHL, RET, RNC, MOV A,B, HLT, LXI B
+        let program = [
+            0xcd, 0xc4, 0xcf, 0xc3, 0xca, 0xe9, 0xc9, 0xd0, 0x78, 0x76, 0x01,
+        ];
+        let expected = [
+            (true, false),
+            (true, false),
+            (true, false),
+            (false, true),
+            (false, true),
+            (false, true),
+            (false, true),
+            (false, true),
+            (false, false),
+            (false, false),
+            (false, false),
+        ];
+        let mut c = I8080Console::default();
+        c.flash(&program);
+        for (pc, expected) in expected.into_iter().enumerate() {
+            c.code_reg_mut(I8080RegisterCode::PcL).reg.load(pc as u8);
+            assert_eq!((c.next_is_call(), c.next_is_branch()), expected);
+        }
+    }
+
+    #[test]
+    fn io_space() {
+        use I8080RegisterCode::Acc;
+        // MVI A,0x2a; OUT 5; MVI A,0; IN 5; IN 6
+        let mut c = I8080Console::default();
+        c.flash(&[0x3e, 0x2a, 0xd3, 0x05, 0x3e, 0x00, 0xdb, 0x05, 0xdb, 0x06]);
+        c.execute();
+        c.execute();
+        assert_eq!(c.io_ports()[5], 0x2a);
+        c.execute();
+        c.execute();
+        assert_eq!(c.code_reg_as_u8(Acc), 0x2a);
+        c.execute();
+        assert_eq!(c.code_reg_as_u8(Acc), 0xff);
+    }
+
+    #[test]
+    fn io_handlers() {
+        use std::rc::Rc;
+        use I8080RegisterCode::Acc;
+        // IN 1; OUT 2; IN 3
+        let mut c = I8080Console::default();
+        c.flash(&[0xdb, 0x01, 0xd3, 0x02, 0xdb, 0x03]);
+        c.set_input_port(1, Box::new(|| 0x5a));
+        let written = Rc::new(Cell::new(None));
+        let sink = Rc::clone(&written);
+        c.set_output_port(2, Box::new(move |data| sink.set(Some(data))));
+        c.execute();
+        assert_eq!(c.code_reg_as_u8(Acc), 0x5a);
+        c.execute();
+        assert_eq!(written.get(), Some(0x5a));
+        c.execute();
+        assert_eq!(c.code_reg_as_u8(Acc), 0xff);
+    }
+
+    #[test]
+    fn peripherals() {
+        use crate::peripheral::Peripheral;
+        use std::rc::Rc;
+        use I8080RegisterCode::Acc;
+        #[derive(Debug, Default)]
+        struct Mock {
+            ticks: Rc<Cell<u64>>,
+            written: Rc<Cell<Option<(u8, u8)>>>,
+        }
+        impl Peripheral for Mock {
+            fn tick(&mut self, cycles: u64) {
+                self.ticks.set(self.ticks.get() + cycles);
+            }
+            fn on_out(&mut self, port: u8, value: u8) {
+                self.written.set(Some((port, value)));
+            }
+            fn on_in(&mut self, port: u8) -> Option<u8> {
+                (port == 0x11).then_some(0x42)
+            }
+        }
+        // MVI A,7; OUT 10; IN 11; IN 12; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[0x3e, 0x07, 0xd3, 0x10, 0xdb, 0x11, 0xdb, 0x12, 0x76]);
+        c.set_input_port(0x11, Box::new(|| 0x5a));
+        c.set_input_port(0x12, Box::new(|| 0x5a));
+        let mock = Mock::default();
+        let (ticks, written) = (Rc::clone(&mock.ticks), Rc::clone(&mock.written));
+        c.add_peripheral(Box::new(mock));
+        c.execute();
+        c.execute();
+        assert_eq!(written.get(), Some((0x10, 0x07)));
+        c.execute();
+        assert_eq!(c.code_reg_as_u8(Acc), 0x42);
+        c.execute();
+        assert_eq!(c.code_reg_as_u8(Acc), 0x5a);
+        c.run();
+        assert_eq!(ticks.get(), 7 + 10 + 10 + 10 + 7);
+        assert_eq!(ticks.get(), c.total_cycles());
+    }
+
+    #[test]
+    fn input_source() {
+        use crate::peripheral::InputSource;
+        use I8080RegisterCode::Acc;
+        #[derive(Debug)]
+        struct Slow(u8);
+        impl InputSource for Slow {
+            fn poll(&mut self, _port: u8) -> Option<u8> {
+                self.0 = self.0.saturating_sub(1);
+                (self.0 == 0).then_some(0x42)
+            }
+        }
+        // IN 1; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[0xdb, 0x01, 0x76]);
+        c.set_input_source(1, Box::new(Slow(3)));
+        for _ in 0..2 {
+            assert_eq!(c.run_until_break(), StopReason::InputPending(1));
+            assert_eq!((c.pc(), c.input_pending()), (0x0000, Some(1)));
+            assert_eq!(c.code_reg_as_u8(Acc), 0x00);
+        }
+        assert_eq!(c.run_until_break(), StopReason::Halted);
+        assert_eq!(c.code_reg_as_u8(Acc), 0x42);
+        assert_eq!(c.input_pending(), None);
+    }
+
+    #[test]
+    fn interrupt_wakes_halt() {
+        use I8080RegisterCode::Acc;
+        // EI; HLT; MVI A,0x22; HLT; ...; 0x08: MVI A,0x11; EI; RET
+        let mut c = I8080ConsoleBuilder::new()
+            .sp(0x0100)
+            .program(&[
+                0xfb, 0x76, 0x3e, 0x22, 0x76, 0, 0, 0, 0x3e, 0x11, 0xfb, 0xc9,
+            ])
+            .build();
+        c.run();
+        assert!(c.is_halted());
+        assert_eq!(c.pc(), 0x0002);
+        assert_eq!(c.step(), StepStatus::Halted);
+        c.interrupt(1);
+        assert!(!c.is_halted());
+        assert_eq!(c.pc(), 0x0008);
+        c.step();
+        assert_eq!(c.code_reg_as_u8(Acc), 0x11);
+        c.run();
+        assert_eq!(c.code_reg_as_u8(Acc), 0x22);
+        assert_eq!(c.pc(), 0x0005);
+        assert!(c.is_halted());
+    }
+
+    #[test]
+    fn opcode_profiling() {
+        // MVI B,3; loop: DCR B; JNZ loop; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[0x06, 0x03, 0x05, 0xc2, 0x02, 0x00, 0x76]);
+        c.execute();
+        assert_eq!(c.opcode_counts(), None);
+        c.enable_profiling();
+        c.run();
+        let counts = c.opcode_counts().unwrap();
+        assert_eq!(
+            [counts[0x06], counts[0x05], counts[0xc2], counts[0x76]],
+            [0, 3, 3, 1]
+        );
+        assert_eq!(counts.iter().sum::<u64>(), 7);
+    }
+
+    #[test]
+    fn fetch_advances_pc() {
+        let mut c = I8080ConsoleBuilder::new()
+            .sp(0x0200)
+            .program(&[0x3e, 0x2a])
+            .build();
+        c.fetch_instruction();
+        assert_eq!(c.regs.bus.get(), 0x3e);
+        assert_eq!((c.pc(), c.sp()), (0x0001, 0x0200));
+        c.fetch_instruction();
+        assert_eq!(c.regs.bus.get(), 0x2a);
+        assert_eq!((c.pc(), c.sp()), (0x0002, 0x0200));
+    }
+
+    #[test]
+    fn state_line() {
+        use crate::alu::StatusFlag::*;
+        let mut c = I8080ConsoleBuilder::new()
+            .acc(0x12)
+            .bc(0x3456)
+            .de(0x789a)
+            .hl(0xbcde)
+            .sp(0xf000)
+            .pc(0x0100)
+            .flags(EnumSet::all())
+            .build();
+        assert_eq!(
+            c.state_line(),
+            "A=12 F=[SZ-A-P-C] BC=3456 DE=789A HL=BCDE SP=F000 PC=0100 cyc=0"
+        );
+        c.set_reg16(
+            I8080RegisterCode16::PSW,
+            I8080Console::<RamB8A16>::flag_scramble(Zero | Carry) as u16,
+        );
+        c.execute();
+        assert_eq!(
+            c.state_line(),
+            "A=00 F=[-Z-----C] BC=3456 DE=789A HL=BCDE SP=F000 PC=0101 cyc=4"
+        );
+    }
+
+    #[test]
+    fn load_store_indirect() {
+        use I8080RegisterCode::Acc;
+        // LDAX B; STAX D; MVI A,0x77; STAX B; LDAX D
+        let mut c = I8080ConsoleBuilder::new()
+            .bc(0x0100)
+            .de(0x0200)
+            .program(&[0x0a, 0x12, 0x3e, 0x77, 0x02, 0x1a])
+            .build();
+        c.memory().lock().unwrap().flash(&[0x5a], 0x0100);
+        assert_eq!(c.execute(), 7);
+        assert_eq!(c.code_reg_as_u8(Acc), 0x5a);
+        assert_eq!(c.execute(), 7);
+        assert_eq!(c.memory().lock().unwrap().read(0x0200), 0x5a);
+        c.execute();
+        c.execute();
+        assert_eq!(c.memory().lock().unwrap().read(0x0100), 0x77);
+        c.execute();
+        assert_eq!(c.code_reg_as_u8(Acc), 0x5a);
+    }
+
+    #[test]
+    fn step_back() {
+        use I8080RegisterCode::Acc;
+        // MVI A,1; STA 0x0100; INR A; PUSH PSW; HLT
+        let mut c = I8080ConsoleBuilder::new()
+            .sp(0x0200)
+            .program(&[0x3e, 0x01, 0x32, 0x00, 0x01, 0x3c, 0xf5, 0x76])
+            .build();
+        assert!(!c.step_back());
+        c.enable_history(3);
+        c.execute();
+        let state = c.snapshot();
+        c.execute();
+        c.execute();
+        assert_eq!(c.memory().lock().unwrap().read(0x0100), 0x01);
+        assert!(c.step_back());
+        assert_eq!(c.code_reg_as_u8(Acc), 0x01);
+        assert!(c.step_back());
+        assert_eq!(c.snapshot(), state);
+        assert_eq!(c.memory().lock().unwrap().read(0x0100), 0x00);
+
+        c.run();
+        assert_eq!(
+            c.memory().lock().unwrap().dump(0x01fe..0x0200),
+            [0x02, 0x02]
+        );
+        // HLT, PUSH PSW, INR A; STA fell out of the history
+        assert!(c.step_back() && c.step_back());
+        assert_eq!(
+            c.memory().lock().unwrap().dump(0x01fe..0x0200),
+            [0x00, 0x00]
+        );
+        assert_eq!(c.sp(), 0x0200);
+        assert!(c.step_back());
+        assert_eq!((c.pc(), c.code_reg_as_u8(Acc)), (0x0005, 0x01));
+        assert!(!c.step_back());
+    }
+
+    #[test]
+    fn execute_opcode() {
+        use crate::alu::StatusFlag::*;
+        use I8080RegisterCode::{Acc, B};
+        let mut c = I8080ConsoleBuilder::new().acc(0x8f).bc(0x7100).build();
+        // ADD B
+        assert_eq!(c.execute_opcode(0x80, &[]), 4);
+        assert_eq!(c.code_reg_as_u8(Acc), 0x00);
+        assert_eq!(c.flags(), Zero | Parity | Carry | AuxiliaryCarry);
+        assert_eq!(c.pc(), 0x0001);
+        // MVI B,0x2a; STA 0x0100
+        c.execute_opcode(0x06, &[0x2a]);
+        assert_eq!(c.code_reg_as_u8(B), 0x2a);
+        c.execute_opcode(0x32, &[0x00, 0x01]);
+        assert_eq!(c.pc(), 0x0006);
+        let memory = c.memory().lock().unwrap();
+        assert_eq!(memory.dump(0x0000..0x0006), [0; 6]);
+        assert_eq!(memory.read(0x0100), 0x00);
+    }
+
+    #[test]
+    fn execute_opcode_as_given() {
+        use I8080RegisterCode::{Acc, B};
+        // a pending RST 7.5 waits for the next instruction
+        let mut c = I8080ConsoleBuilder::new().acc(0x01).bc(0x0200).build();
+        c.set_variant(I8080Variant::I8085);
+        c.set_reg16(I8080RegisterCode16::SP, 0x1000);
+        c.rst_unmasked = 0x07;
+        c.interrupt_enabled.set(true);
+        c.trigger_rst75();
+        c.execute_opcode(0x80, &[]);
+        assert_eq!((c.code_reg_as_u8(Acc), c.pc()), (0x03, 0x0001));
+        c.execute();
+        assert_eq!(c.pc(), 0x003c);
+
+        // so does a BDOS call at PC
+        let mut c = I8080Console::<RamB8A16>::default();
+        c.load_com(&[]);
+        c.set_reg16(I8080RegisterCode16::PC, 0x0005);
+        c.execute_opcode(0x3c, &[]);
+        assert_eq!((c.code_reg_as_u8(Acc), c.pc()), (0x01, 0x0006));
+
+        // missing operands read as 0, not from memory
+        let mut c = I8080Console::default();
+        c.flash(&[0x00, 0x34, 0x12]);
+        c.execute_opcode(0xc3, &[]);
+        assert_eq!(c.pc(), 0x0000);
+        c.execute_opcode(0x06, &[0x2a, 0xff]);
+        assert_eq!((c.code_reg_as_u8(B), c.pc()), (0x2a, 0x0002));
+    }
+
+    #[test]
+    fn branch_after_compare() {
+        for (acc, operand) in [
+            (0x10, 0x10),
+            (0x10, 0x20),
+            (0x20, 0x10),
+            (0x80, 0x01),
+            (0x00, 0x81),
+        ] {
+            let mut c = I8080ConsoleBuilder::new().acc(acc).build();
+            // CPI operand
+            c.execute_opcode(0xfe, &[operand]);
+            let diff = acc.wrapping_sub(operand);
+            let (zero, carry) = (acc == operand, acc < operand);
+            let (sign, even) = (diff & 0x80 != 0, diff.count_ones() % 2 == 0);
+            let jumps = [
+                (0xc2, "JNZ", !zero),
+                (0xca, "JZ", zero),
+                (0xd2, "JNC", !carry),
+                (0xda, "JC", carry),
+                (0xe2, "JPO", !even),
+                (0xea, "JPE", even),
+                (0xf2, "JP", !sign),
+                (0xfa, "JM", sign),
+            ];
+            for (opcode, name, taken) in jumps {
+                c.set_reg16(I8080RegisterCode16::PC, 0x0000);
+                c.execute_opcode(opcode, &[0x00, 0x10]);
+                let expected = if taken { 0x1000 } else { 0x0003 };
+                assert_eq!(
+                    c.pc(),
+                    expected,
+                    "CPI {operand:#04x} with A={acc:#04x}; {name}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inx_dcx_keep_flags() {
+        use I8080RegisterCode16::*;
+        for flags in [EnumSet::all(), EnumSet::empty()] {
+            let mut c = I8080ConsoleBuilder::new().bc(0xffff).flags(flags).build();
+            // INX B; DCX B; DCX D; INX SP
+            c.execute_opcode(0x03, &[]);
+            assert_eq!(c.reg16(BC), 0x0000);
+            c.execute_opcode(0x0b, &[]);
+            assert_eq!(c.reg16(BC), 0xffff);
+            c.execute_opcode(0x1b, &[]);
+            assert_eq!(c.reg16(DE), 0xffff);
+            c.execute_opcode(0x33, &[]);
+            assert_eq!(c.sp(), 0x0001);
+            assert_eq!(c.flags(), flags);
+        }
+    }
+
+    #[test]
+    fn memset_memcmp() {
+        let mut c = I8080Console::<RamB8A16>::default();
+        c.memory().lock().unwrap().flash(&[0xe5; 0x400], 0x8000);
+        c.memset(0x1000, 0xe5, 0x400);
+        assert!(c.memcmp(0x1000, 0x8000, 0x400));
+        assert!(!c.memcmp(0x1000, 0x0000, 0x400));
+        c.memset(0x13ff, 0x00, 1);
+        assert!(!c.memcmp(0x1000, 0x8000, 0x400));
+        assert!(c.memcmp(0x1000, 0x8000, 0x3ff));
+    }
+
+    #[test]
+    fn bus_probes() {
+        // MVI A,0x2a; STA 0x0100
+        let mut c = I8080Console::<RamB8A16>::default();
+        c.flash(&[0x3e, 0x2a, 0x32, 0x00, 0x01]);
+        c.execute();
+        assert_eq!((c.address_bus(), c.data_bus()), (0x0001, 0x2a));
+        c.execute();
+        assert_eq!((c.address_bus(), c.data_bus()), (0x0100, 0x2a));
+        c.set_data_bus(0x5a);
+        c.store();
+        assert_eq!(c.memory().lock().unwrap().read(0x0100), 0x5a);
+    }
+
+    #[cfg(feature = "z80")]
+    #[test]
+    fn exchange_register_banks() {
+        use I8080RegisterCode::*;
+        use I8080RegisterCode16::*;
+        let mut c = I8080ConsoleBuilder::new()
+            .acc(0x12)
+            .bc(0x3456)
+            .de(0x789a)
+            .hl(0xbcde)
+            .build();
+        c.exchange_register_banks();
+        assert_eq!([BC, DE, HL].map(|code| c.reg16(code)), [0; 3]);
+        assert_eq!(c.code_reg_as_u8(Acc), 0x12);
+        let shadow = c.shadow_registers();
+        assert_eq!(
+            [shadow.b, shadow.c, shadow.d, shadow.e, shadow.h, shadow.l],
+            [0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde]
+        );
+        c.exchange_register_banks();
+        assert_eq!(
+            [BC, DE, HL].map(|code| c.reg16(code)),
+            [0x3456, 0x789a, 0xbcde]
+        );
+    }
+
+    #[test]
+    fn lxi_little_endian() {
+        // LXI B,0x1234; LXI SP,0xF000
+        use I8080RegisterCode16::*;
+        let mut c = I8080Console::<RamB8A16>::default();
+        c.flash(&[0x01, 0x34, 0x12, 0x31, 0x00, 0xf0]);
+        assert_eq!(c.execute(), 10);
+        assert_eq!(c.reg16(BC), 0x1234);
+        c.execute();
+        assert_eq!(c.reg16(SP), 0xf000);
+    }
+
+    #[test]
+    fn execute_decoded() {
+        use decode::{Instruction::*, Operand, Pair, Reg8};
+        use I8080RegisterCode16::*;
+        let mut c = I8080Console::<RamB8A16>::default();
+        assert_eq!(c.execute_decoded(Lxi(Pair::HL, 0x1234)), 10);
+        assert_eq!(c.reg16(HL), 0x1234);
+        c.execute_decoded(Mvi(Operand::M, 0x2a));
+        assert_eq!(c.memory().lock().unwrap().read(0x1234), 0x2a);
+        c.execute_decoded(Mov(Operand::Reg(Reg8::B), Operand::M));
+        assert_eq!(c.reg16(BC), 0x2a00);
+        assert_eq!(c.pc(), 0x0006);
+    }
+
+    #[test]
+    fn shld_lhld() {
+        use I8080RegisterCode16::*;
+        let mut c = I8080Console::<RamB8A16>::default();
+        c.set_reg16(HL, 0xbeef);
+        assert_eq!(c.execute_opcode(0x22, &[0xff, 0x20]), 16);
+        {
+            let memory = c.memory().lock().unwrap();
+            assert_eq!(memory.read(0x20ff), 0xef);
+            assert_eq!(memory.read(0x2100), 0xbe);
+        }
+        c.set_reg16(HL, 0);
+        assert_eq!(c.execute_opcode(0x2a, &[0xff, 0x20]), 16);
+        assert_eq!(c.reg16(HL), 0xbeef);
+        assert_eq!(c.pc(), 0x0006);
+    }
+
+    #[test]
+    fn psw_accessor() {
+        use I8080RegisterCode::Acc;
+        use I8080RegisterCode16::*;
+        let mut c = I8080Console::<RamB8A16>::default();
+        c.set_reg16(PSW, 0x12ff);
+        assert_eq!(c.code_reg_as_u8(Acc), 0x12);
+        assert_eq!(c.flag_status(), EnumSet::all());
+        assert_eq!(c.reg16(PSW), 0x12d7);
+        c.set_reg16(PSW, 0x3400);
+        assert_eq!(c.reg16(PSW), 0x3402);
+        assert!(c.flag_status().is_empty());
+        c.set_reg16(HL, 0xbeef);
+        assert_eq!(c.reg16(HL), 0xbeef);
+        assert_eq!("PSW".parse(), Ok(PSW));
+        assert_eq!("psw".parse(), Ok(PSW));
+        assert_eq!("hl".parse(), Ok(HL));
+        assert!("AF".parse::<I8080RegisterCode16>().is_err());
+    }
+
+    #[test]
+    fn reset_vector() {
+        use I8080RegisterCode16::PC;
+        let mut c = I8080Console::default();
+        c.flash(&[0x00, 0x00, 0x00]);
+        c.memory.lock().unwrap().write(0x0100, 0x76);
+        c.set_reset_vector(0x0100);
+        c.execute();
+        c.reset();
+        assert_eq!(c.reg16(PC), 0x0100);
+        let fetch = c.step_cycle();
+        assert_eq!(fetch.cycle_type, I8080CycleType::OpcodeFetch);
+        assert_eq!((fetch.address, fetch.data), (0x0100, 0x76));
+    }
+
+    #[test]
+    fn reset_clears_registers() {
+        // MVI A,0x0f; MVI B,0x12; MVI L,0x34; STC; EI; HLT
+        let program = [0x3e, 0x0f, 0x06, 0x12, 0x2e, 0x34, 0x37, 0xfb, 0x76];
+        let mut c = I8080ConsoleBuilder::new()
+            .sp(0x0200)
+            .program(&program)
+            .build();
+        c.run();
+        c.reset();
+        assert_eq!(
+            c.snapshot(),
+            I8080State {
+                flag: 0x02,
+                ..Default::default()
+            }
+        );
+        assert_eq!(c.reg16(I8080RegisterCode16::PSW), 0x0002);
+        assert_eq!(c.memory().lock().unwrap().dump(0..9), program);
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        // MVI A,0x0f; MVI B,0x12; MVI C,0x34; PUSH B; INR A; DCX B; XCHG; EI; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[
+            0x3e, 0x0f, 0x06, 0x12, 0x0e, 0x34, 0xc5, 0x3c, 0x0b, 0xeb, 0xfb, 0x76,
+        ]);
+        c.set_reg16(I8080RegisterCode16::SP, 0x0100);
+        for _ in 0..4 {
+            c.execute();
+        }
+        let state = c.snapshot();
+        assert_eq!((state.a, state.b, state.c), (0x0f, 0x12, 0x34));
+        assert_eq!((state.sp, state.pc), (0x00fe, 0x0007));
+        c.run();
+        assert_ne!(c.snapshot(), state);
+        c.restore(&state);
+        assert_eq!(c.snapshot(), state);
+        assert_eq!(c.memory().lock().unwrap().read(0x00fe), 0x34);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot_serde() {
+        use crate::alu::StatusFlag;
+        use I8080RegisterCode16::*;
+        let mut c = I8080Console::<RamB8A16>::default();
+        c.set_reg16(PSW, 0x12d7);
+        c.set_reg16(HL, 0xbeef);
+        c.set_reg16(SP, 0xfffe);
+        let state = c.snapshot();
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(serde_json::from_str::<I8080State>(&json).unwrap(), state);
+
+        let json = serde_json::to_string(&c.flags()).unwrap();
+        assert_eq!(json, "31");
+        let flags: EnumSet<StatusFlag> = serde_json::from_str(&json).unwrap();
+        assert_eq!(flags, c.flags());
+
+        let codes = serde_json::to_string(&(I8080RegisterCode::Acc, PSW)).unwrap();
+        assert_eq!(
+            serde_json::from_str::<(I8080RegisterCode, I8080RegisterCode16)>(&codes).unwrap(),
+            (I8080RegisterCode::Acc, PSW)
+        );
+    }
+
+    #[test]
+    fn adc_half_carry() {
+        use I8080RegisterCode::{Acc, B};
+        for (carry, expected) in [(true, (0x10, true)), (false, (0x0f, false))] {
+            // ADC B
+            let mut c = I8080Console::default();
+            c.flash(&[0x88]);
+            c.code_reg_mut(Acc).reg.load(0x0f);
+            c.code_reg_mut(B).reg.load(0x00);
+            if carry {
+                c.flag_set(StatusFlag::Carry);
+            }
+            c.execute();
+            let half_carry = c.flag_status().contains(StatusFlag::AuxiliaryCarry);
+            assert_eq!((c.code_reg_as_u8(Acc), half_carry), expected);
+        }
+    }
+
+    #[test]
+    fn carry_in_at_boundary() {
+        use StatusFlag::*;
+        let cases = [
+            // ACI
+            (
+                0xce,
+                0xff,
+                0x00,
+                true,
+                0x00,
+                Zero | Parity | Carry | AuxiliaryCarry,
+            ),
+            (0xce, 0xff, 0x00, false, 0xff, Sign | Parity),
+            (
+                0xce,
+                0x00,
+                0xff,
+                true,
+                0x00,
+                Zero | Parity | Carry | AuxiliaryCarry,
+            ),
+            (0xce, 0x00, 0xff, false, 0xff, Sign | Parity),
+            // SBI
+            (0xde, 0x00, 0xff, true, 0x00, Zero | Parity | Carry),
+            (0xde, 0x00, 0xff, false, 0x01, EnumSet::only(Carry)),
+            (0xde, 0xff, 0xff, true, 0xff, Sign | Parity | Carry),
+            (0xde, 0xff, 0x00, true, 0xfe, Sign | AuxiliaryCarry),
+            (0xde, 0x00, 0x00, true, 0xff, Sign | Parity | Carry),
+        ];
+        for (opcode, acc, operand, carry, result, flags) in cases {
+            let mut c = I8080ConsoleBuilder::new()
+                .acc(acc)
+                .flags(if carry {
+                    Carry.into()
+                } else {
+                    EnumSet::empty()
+                })
+                .program(&[opcode, operand])
+                .build();
+            c.execute();
+            let name = format!("{opcode:02X} {acc:02X},{operand:02X} carry {carry}");
+            assert_eq!(c.code_reg_as_u8(I8080RegisterCode::Acc), result, "{name}");
+            assert_eq!(c.flags(), flags, "{name}");
+        }
+    }
+
+    #[test]
+    fn signals() {
+        // MVI A,0x2a; STA 0x0100; EI; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[0x3e, 0x2a, 0x32, 0x00, 0x01, 0xfb, 0x76]);
+        let signals = c.signals();
+        c.execute();
+        assert!(!signals.write.get());
+        assert!(signals.data_in.get());
+        c.execute();
+        assert!(signals.write.get());
+        assert!(!signals.data_in.get());
+        assert!(!signals.interrupt_enabled.get());
+        c.execute();
+        assert!(!signals.write.get());
+        assert!(signals.interrupt_enabled.get());
+        assert!(!signals.halted.get());
+        c.execute();
+        assert!(signals.halted.get());
+    }
+
+    #[test]
+    fn randomize_registers() {
+        let randomized = |seed| {
+            let mut c = I8080Console::<RamB8A16>::default();
+            c.randomize_registers(seed);
+            c.snapshot()
+        };
+        assert_eq!(randomized(42), randomized(42));
+        assert_ne!(randomized(42), randomized(43));
+        assert_ne!(randomized(0), I8080State::default());
+        for seed in 0..64 {
+            let state = randomized(seed);
+            assert_eq!(state.flag & 0x2a, 0x02);
+            assert_eq!(state.pc, 0);
+        }
+    }
+
+    #[test]
+    fn pchl_sphl() {
+        use I8080RegisterCode16::{HL, PC, SP};
+        // SPHL; PCHL
+        let mut c = I8080Console::default();
+        c.flash(&[0xf9, 0xe9]);
+        c.set_reg16(HL, 0x1234);
+        assert_eq!(c.execute(), 5);
+        assert_eq!(c.reg16(SP), 0x1234);
+        assert_eq!(c.reg16(PC), 0x0001);
+        assert_eq!(c.execute(), 5);
+        assert_eq!(c.reg16(PC), 0x1234);
+        assert_eq!(c.reg16(HL), 0x1234);
+        assert_eq!(c.total_cycles(), 10);
+    }
+
+    #[test]
+    fn call_stack() {
+        use I8080RegisterCode16::{PC, SP};
+        let mut c = I8080Console::default();
+        // NOP; CALL 0x1234
+        c.flash(&[0x00, 0xcd, 0x34, 0x12]);
+        c.set_reg16(SP, 0x0100);
+        c.execute();
+        c.execute();
+        assert_eq!(c.reg16(PC), 0x1234);
+        assert_eq!(c.reg16(SP), 0x00fe);
+        let memory = c.memory().lock().unwrap();
+        assert_eq!([memory.read(0x00fe), memory.read(0x00ff)], [0x04, 0x00]);
+        assert_eq!(memory.read(0x0100), 0x00);
+    }
+
+    #[test]
+    fn step_halted() {
+        use I8080RegisterCode16::PC;
+        // NOP; HLT; INR A
+        let mut c = I8080Console::default();
+        c.flash(&[0x00, 0x76, 0x3c]);
+        assert_eq!(c.step(), StepStatus::Executed(4));
+        assert_eq!(c.step(), StepStatus::Executed(7));
+        for _ in 0..3 {
+            assert_eq!(c.step(), StepStatus::HaltedForever);
+            assert_eq!(c.reg16(PC), 0x0002);
+            assert_eq!(c.register(I8080RegisterCode::Acc), 0);
+        }
+        assert_eq!(c.total_cycles(), 11);
+        c.enable_interrupt();
+        assert_eq!(c.step(), StepStatus::Halted);
+        c.set_reg16(I8080RegisterCode16::SP, 0x0100);
+        c.interrupt(7);
+        assert_eq!(c.reg16(PC), 0x0038);
+        assert_eq!(c.step(), StepStatus::Executed(4));
+    }
+
+    #[test]
+    fn debugger_accessors() {
+        use I8080RegisterCode::{Acc, B};
+        use I8080RegisterCode16::{BC, PSW};
+        // MVI B,0xfe; INR B; LDA 0x0000
+        let mut c = I8080Console::default();
+        c.flash(&[0x06, 0xfe, 0x04, 0x3a, 0x00, 0x00]);
+        c.set_reg16(I8080RegisterCode16::SP, 0x0100);
+        let mut pcs = vec![c.pc()];
+        for _ in 0..3 {
+            assert!(matches!(c.step(), StepStatus::Executed(_)));
+            pcs.push(c.pc());
+        }
+        assert_eq!(pcs, [0x0000, 0x0002, 0x0003, 0x0006]);
+        assert_eq!(c.register(B), 0xff);
+        assert_eq!(c.register16(BC), 0xff00);
+        assert_eq!(c.register(Acc), 0x06);
+        assert_eq!(c.register16(PSW) >> 8, 0x06);
+        assert_eq!(c.sp(), 0x0100);
+        assert_eq!(c.flags(), StatusFlag::Sign | StatusFlag::Parity);
+    }
+
+    #[test]
+    fn custom_memory() {
+        #[derive(Debug, Default)]
+        struct LoggingMemory {
+            ram: RamB8A16,
+            writes: Vec<(u16, u8)>,
+        }
+        impl Memory for LoggingMemory {
+            type Data = u8;
+            type Address = u16;
+            fn write(&mut self, address: u16, data: u8) {
+                self.writes.push((address, data));
+                self.ram.write(address, data)
+            }
+            fn read(&self, address: u16) -> u8 {
+                self.ram.read(address)
+            }
+        }
+        let mut memory = LoggingMemory::default();
+        // MVI A,0x2a; STA 0x0100; PUSH B; HLT
+        memory
+            .ram
+            .flash(&[0x3e, 0x2a, 0x32, 0x00, 0x01, 0xc5, 0x76], 0);
+        let mut c = I8080Console {
+            memory: Arc::new(Mutex::new(memory)),
+            ..Default::default()
+        };
+        c.set_reg16(I8080RegisterCode16::SP, 0x0200);
+        c.run();
+        let memory = c.memory().lock().unwrap();
+        assert_eq!(memory.writes[0], (0x0100, 0x2a));
+        assert_eq!(memory.writes.len(), 3);
+        assert_eq!(memory.read(0x0100), 0x2a);
+        let _flat: I8080Console = I8080Console::default();
+    }
+
+    #[test]
+    fn flag_encoding_round_trip() {
+        type Console = I8080Console<RamB8A16>;
+        for bits in 0..32 {
+            let set = EnumSet::<StatusFlag>::from_u32(bits);
+            let byte = Console::flag_scramble(set);
+            // S Z 0 AC 0 P 1 C
+            assert_eq!(byte & 0x2a, 0x02, "flags {byte:#04x}");
+            assert_eq!(byte.count_ones(), set.len() as u32 + 1);
+            assert_eq!(Console::flag_collect(byte), set);
+        }
+        for byte in 0..=255u8 {
+            let round = Console::flag_scramble(Console::flag_collect(byte));
+            assert_eq!(round, byte & 0xd5 | 0x02, "flags {byte:#04x}");
+        }
+    }
+
+    #[test]
+    fn read_only_memory() {
+        let mut memory = RamB8A16::new();
+        memory.set_read_only(0..0x100);
+        // MVI A,0x2a; STA 0x0050; STA 0x0150; HLT
+        memory.flash(&[0x3e, 0x2a, 0x32, 0x50, 0x00, 0x32, 0x50, 0x01, 0x76], 0);
+        let mut c = I8080Console {
+            memory: Arc::new(Mutex::new(memory)),
+            ..Default::default()
+        };
+        c.run();
+        let memory = c.memory().lock().unwrap();
+        assert_eq!(memory.read(0x0050), 0x00);
+        assert_eq!(memory.read(0x0150), 0x2a);
+        assert_eq!(memory.read(0x0001), 0x2a);
+        assert_eq!(memory.protected_write_count(), 1);
+    }
+
+    #[test]
+    fn builder() {
+        use I8080RegisterCode16::*;
+        let c = I8080ConsoleBuilder::new().hl(0x1234).sp(0xfffe).build();
+        assert_eq!((c.reg16(HL), c.reg16(SP)), (0x1234, 0xfffe));
+
+        // ADD B; HLT
+        let mut c = I8080ConsoleBuilder::new()
+            .acc(0x10)
+            .bc(0x2000)
+            .de(0x5678)
+            .pc(0x0100)
+            .flags(StatusFlag::Carry | StatusFlag::Zero)
+            .program(&[0x76])
+            .build();
+        assert_eq!(c.reg16(PSW), 0x1043);
+        assert_eq!(c.reg16(DE), 0x5678);
+        c.memory().lock().unwrap().flash(&[0x80, 0x76], 0x0100);
+        c.run();
+        assert_eq!(c.register(I8080RegisterCode::Acc), 0x30);
+        assert_eq!(c.pc(), 0x0102);
+    }
+
+    #[test]
+    fn push_pop() {
+        use I8080RegisterCode::*;
+        use I8080RegisterCode16::*;
+        // PUSH H; POP B; PUSH PSW; POP D; HLT
+        let mut c = I8080ConsoleBuilder::new()
+            .hl(0x1234)
+            .sp(0x0200)
+            .acc(0x56)
+            .flags(StatusFlag::Carry.into())
+            .program(&[0xe5, 0xc1, 0xf5, 0xd1, 0x76])
+            .build();
+        c.run();
+        assert_eq!((c.register(B), c.register(C)), (0x12, 0x34));
+        assert_eq!(c.reg16(DE), 0x5603);
+        assert_eq!(c.sp(), 0x0200);
+        let memory = c.memory().lock().unwrap();
+        assert_eq!([memory.read(0x01fe), memory.read(0x01ff)], [0x03, 0x56]);
+    }
+
+    #[test]
+    fn pop_psw_normalizes_flags() {
+        use I8080RegisterCode16::*;
+        // POP PSW; PUSH PSW; POP PSW; POP PSW; PUSH PSW; HLT
+        let mut c = I8080ConsoleBuilder::new()
+            .sp(0x01fc)
+            .program(&[0xf1, 0xf5, 0xf1, 0xf1, 0xf5, 0x76])
+            .build();
+        c.memory()
+            .lock()
+            .unwrap()
+            .flash(&[0xff, 0x12, 0x28, 0x34], 0x01fc);
+        c.step();
+        assert_eq!(c.reg16(PSW), 0x12d7);
+        assert_eq!(c.flags(), EnumSet::all());
+        c.step();
+        assert_eq!(c.memory().lock().unwrap().read(0x01fc), 0xd7);
+        c.step();
+        c.step();
+        assert_eq!(c.reg16(PSW), 0x3402);
+        assert!(c.flags().is_empty());
+        c.step();
+        assert_eq!(c.memory().lock().unwrap().read(0x01fe), 0x02);
+    }
+
+    #[test]
+    fn complement_keeps_flags() {
+        // CMA; HLT
+        let mut c = I8080ConsoleBuilder::new()
+            .acc(0x0f)
+            .flags(StatusFlag::Carry | StatusFlag::Zero)
+            .program(&[0x2f, 0x76])
+            .build();
+        c.run();
+        assert_eq!(c.register(I8080RegisterCode::Acc), 0xf0);
+        assert_eq!(c.flags(), StatusFlag::Carry | StatusFlag::Zero);
+    }
+
+    #[test]
+    fn increment_keeps_carry() {
+        use StatusFlag::*;
+        // INR B; DCR C; HLT
+        let mut c = I8080ConsoleBuilder::new()
+            .bc(0xff01)
+            .flags(Carry.into())
+            .program(&[0x04, 0x0d, 0x76])
+            .build();
+        c.step();
+        assert_eq!(c.register(I8080RegisterCode::B), 0x00);
+        assert_eq!(c.flags(), Carry | Zero | Parity | AuxiliaryCarry);
+        c.step();
+        assert_eq!(c.register(I8080RegisterCode::C), 0x00);
+        assert_eq!(c.flags(), Carry | Zero | Parity | AuxiliaryCarry);
+    }
+
+    #[test]
+    fn dispatch_follows_variant() {
+        use I8080RegisterCode::Acc;
+        // RIM; RIM; HLT
+        let mut c = I8080ConsoleBuilder::new()
+            .acc(0x55)
+            .program(&[0x20, 0x20, 0x76])
+            .build();
+        c.step();
+        assert_eq!(c.register(Acc), 0x55);
+        // the dispatch table is built by now, yet its step checks the variant
+        c.set_variant(I8080Variant::I8085);
+        c.step();
+        assert_eq!(c.register(Acc), 0x07);
+    }
+
+    #[test]
+    fn cpm_com() {
+        use std::cell::RefCell;
+        let com = [
+            0x0e, 0x09, // MVI C,9
+            0x16, 0x01, // MVI D,0x01
+            0x1e, 0x11, // MVI E,0x11
+            0xcd, 0x05, 0x00, // CALL 5
+            0x0e, 0x02, // MVI C,2
+            0x1e, b'!', // MVI E,'!'
+            0xcd, 0x05, 0x00, // CALL 5
+            0xc9, // RET
+            b'h', b'i', b'$',
+        ];
+        let output = Rc::new(RefCell::new(vec![]));
+        let mut c = I8080Console::<RamB8A16>::default();
+        let sink = output.clone();
+        c.set_output_port(
+            CPM_CONSOLE_PORT,
+            Box::new(move |b| sink.borrow_mut().push(b)),
+        );
+        c.load_com(&com);
+        assert_eq!(c.pc(), 0x0100);
+        assert_eq!(c.run_limited(100), RunResult::Halted);
+        assert_eq!(*output.borrow(), b"hi!");
+        assert_eq!((c.pc(), c.sp()), (0x0000, 0xff00));
+
+        // a reset console runs from 0x0000 again instead of warm booting.
+        c.reset();
+        c.execute();
+        assert!(!c.is_halted());
+        assert_eq!(c.pc(), 0x0001);
+    }
+
+    #[test]
+    fn cpm_com_over_read_only() {
+        let mut memory = RamB8A16::new();
+        memory.set_read_only(0x0100..0x0200);
+        memory.set_read_only(0xfe00..0xff00);
+        memory.flash(&[0xff, 0xff], 0xfefe);
+        let mut c = I8080Console {
+            memory: Arc::new(Mutex::new(memory)),
+            ..Default::default()
+        };
+        // MVI A,0x2a; RET
+        c.load_com(&[0x3e, 0x2a, 0xc9]);
+        let memory = c.memory().lock().unwrap();
+        assert_eq!(memory.dump(0x0100..0x0103), [0x3e, 0x2a, 0xc9]);
+        assert_eq!(memory.dump(0xfefe..0xff00), [0x00, 0x00]);
+        assert_eq!(memory.protected_write_count(), 0);
+    }
+
+    #[test]
+    fn cpm_com_unterminated_string() {
+        use std::cell::RefCell;
+        let com = [
+            0x0e, 0x09, // MVI C,9
+            0x11, 0x00, 0x02, // LXI D,0x0200
+            0xcd, 0x05, 0x00, // CALL 5
+            0xc9, // RET
+        ];
+        let output = Rc::new(RefCell::new(vec![]));
+        let mut c = I8080Console::<RamB8A16>::default();
+        let sink = output.clone();
+        c.set_output_port(
+            CPM_CONSOLE_PORT,
+            Box::new(move |b| sink.borrow_mut().push(b)),
+        );
+        c.load_com(&com);
+        assert_eq!(c.run_limited(100), RunResult::Halted);
+        assert_eq!(output.borrow().len(), 0x10000);
+        assert_eq!(c.pc(), 0x0000);
+    }
+
+    #[test]
+    fn undocumented_opcodes() {
+        let twins = [
+            (0x08, 0x00),
+            (0x10, 0x00),
+            (0x18, 0x00),
+            (0x20, 0x00),
+            (0x28, 0x00),
+            (0x30, 0x00),
+            (0x38, 0x00),
+            (0xcb, 0xc3),
+            (0xd9, 0xc9),
+            (0xdd, 0xcd),
+            (0xed, 0xcd),
+            (0xfd, 0xcd),
+        ];
+        let run = |opcode: u8| {
+            let mut c = I8080ConsoleBuilder::new()
+                .sp(0x01fe)
+                .program(&[opcode, 0x34, 0x12])
+                .build();
+            c.memory().lock().unwrap().flash(&[0x78, 0x56], 0x01fe);
+            let states = c.execute();
+            let stack = c.memory().lock().unwrap().dump(0x01fc..0x0200).to_vec();
+            (c.snapshot(), states, stack)
+        };
+        for (undocumented, documented) in twins {
+            assert_eq!(run(undocumented), run(documented), "{undocumented:#04x}");
+        }
+        // RET
+        let (state, states, _) = run(0xd9);
+        assert_eq!((state.pc, state.sp, states), (0x5678, 0x0200, 10));
+    }
+
+    #[test]
+    fn exchange_stack_top() {
+        // XTHL
+        let mut c = I8080ConsoleBuilder::new()
+            .hl(0x1234)
+            .sp(0x0200)
+            .program(&[0xe3])
+            .build();
+        c.memory().lock().unwrap().flash(&[0x78, 0x56], 0x0200);
+        assert_eq!(c.execute(), 18);
+        assert_eq!(c.reg16(I8080RegisterCode16::HL), 0x5678);
+        assert_eq!(c.sp(), 0x0200);
+        assert_eq!(
+            c.memory().lock().unwrap().dump(0x0200..0x0202),
+            [0x34, 0x12]
+        );
+    }
+
+    #[test]
+    fn conditional_call_and_return() {
+        use crate::alu::StatusFlag::Zero;
+        // CZ 0010; RZ
+        let program = [0xcc, 0x10, 0x00];
+        let console = |flags| {
+            let c = I8080ConsoleBuilder::new()
+                .sp(0x0200)
+                .flags(flags)
+                .program(&program)
+                .build();
+            c.memory().lock().unwrap().flash(&[0xc8], 0x0010);
+            c
+        };
+
+        let mut c = console(EnumSet::empty());
+        assert_eq!(c.execute(), 11);
+        assert_eq!((c.pc(), c.sp()), (0x0003, 0x0200));
+
+        let mut c = console(Zero.into());
+        assert_eq!(c.execute(), 17);
+        assert_eq!((c.pc(), c.sp()), (0x0010, 0x01fe));
+        assert_eq!(
+            c.memory().lock().unwrap().dump(0x01fe..0x0200),
+            [0x03, 0x00]
+        );
+        assert_eq!(c.execute(), 11);
+        assert_eq!((c.pc(), c.sp()), (0x0003, 0x0200));
+
+        let mut c = console(Zero.into());
+        c.execute();
+        c.set_reg16(I8080RegisterCode16::PSW, 0);
+        assert_eq!(c.execute(), 5);
+        assert_eq!((c.pc(), c.sp()), (0x0011, 0x01fe));
+    }
+
+    #[test]
+    fn write_watch() {
+        let hits = Arc::new(Mutex::new(vec![]));
+        let mut memory = RamB8A16::new();
+        let log = hits.clone();
+        memory.set_write_watch(
+            0x1234,
+            Box::new(move |a, d| log.lock().unwrap().push((a, d))),
+        );
+        // MVI A,0x2a; STA 0x1234; STA 0x1235; HLT
+        memory.flash(&[0x3e, 0x2a, 0x32, 0x34, 0x12, 0x32, 0x35, 0x12, 0x76], 0);
+        memory.flash(&[0xff], 0x1234);
+        let mut c = I8080Console {
+            memory: Arc::new(Mutex::new(memory)),
+            ..Default::default()
+        };
+        c.run();
+        assert_eq!(*hits.lock().unwrap(), [(0x1234, 0x2a)]);
+    }
+
+    #[test]
+    fn double_add() {
+        use I8080RegisterCode16::*;
+        let dad = |opcode: u8, pair: I8080RegisterCode16, hl: u16, rp: u16| {
+            let mut memory = RamB8A16::new();
+            memory.flash(&[opcode, 0x76], 0);
+            let mut c = I8080Console {
+                memory: Arc::new(Mutex::new(memory)),
+                ..Default::default()
+            };
+            // Z set, CY set: DAD must leave Z alone and recompute CY
+            c.set_reg16(PSW, 0x0041);
+            c.set_reg16(pair, rp);
+            c.set_reg16(HL, hl);
+            c.run();
+            let flags = c.flags();
+            assert!(flags.contains(StatusFlag::Zero));
+            (c.reg16(HL), flags.contains(StatusFlag::Carry))
+        };
+        assert_eq!(dad(0x09, BC, 0x1234, 0x1111), (0x2345, false));
+        assert_eq!(dad(0x09, BC, 0xf000, 0x1001), (0x0001, true));
+        assert_eq!(dad(0x29, HL, 0x4321, 0x4321), (0x8642, false));
+        assert_eq!(dad(0x29, HL, 0x8001, 0x8001), (0x0002, true));
+        assert_eq!(dad(0x39, SP, 0x0100, 0x0200), (0x0300, false));
+        assert_eq!(dad(0x39, SP, 0xffff, 0x0001), (0x0000, true));
+    }
+
+    #[test]
+    fn register_pair() {
+        use I8080RegisterCode::*;
+        for pair in [[Acc, Flag], [B, C], [D, E], [H, L], [W, Z]] {
+            for code in pair {
+                assert_eq!(code.pair(), pair);
+            }
+        }
+    }
+
+    #[test]
+    fn push_pop_inverse() {
+        use I8080RegisterCode16::*;
+        let mut c = I8080Console::default();
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut random = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as u16
+        };
+        let load16 = |c: &mut I8080Console<RamB8A16>, code: I8080RegisterCode16, x: u16| {
+            code.split()
+                .into_iter()
+                .zip(x.to_be_bytes())
+                .for_each(|(r, x)| c.code_reg_mut(r).reg.load(x))
+        };
+        for _ in 0..1000 {
+            let sp = random() % 0xfe00 + 0x0100;
+            load16(&mut c, SP, sp);
+            for code in [BC, DE, HL, PSW] {
+                let mut x = random();
+                if code == PSW {
+                    // bit 1 is always set, bits 3 and 5 always reset
+                    x = x & 0xffd7 | 0x0002;
+                }
+                load16(&mut c, code, x);
+                c.push_reg16(code);
+                load16(&mut c, code, !x);
+                c.pop_reg16(code);
+                assert_eq!(c.code_reg16_as_u16(code), x, "{code:?}");
+                assert_eq!(c.code_reg16_as_u16(SP), sp);
+            }
+        }
+    }
+
+    #[test]
+    fn i8085_hardware_rst() {
+        use I8080RegisterCode::Acc;
+        use I8080RegisterCode16::{PC, SP};
+        // EI; MVI A,0x0e; SIM; NOP; RIM
+        let program = [0xfb, 0x3e, 0x0e, 0x30, 0x00, 0x20];
+        let mut c = I8080Console::default();
+        c.set_variant(I8080Variant::I8085);
+        c.flash(&program);
+        c.load_reg16(SP, 0x1000);
+        (0..3).for_each(|_| {
+            c.execute();
+        });
+        // RST 6.5 is masked and ignored
+        c.trigger_rst65();
+        c.execute();
+        assert_eq!(c.code_reg16_as_u16(PC), 0x0005);
+        // RST 7.5 is masked but latched
+        c.trigger_rst75();
+        c.execute();
+        assert_eq!(c.code_reg16_as_u16(PC), 0x0006);
+        assert_eq!(c.code_reg_as_u8(Acc), 0b0100_1110);
+        c.trigger_rst55();
+        c.execute();
+        assert_eq!(c.code_reg16_as_u16(PC), 0x002c);
+        assert_eq!(c.code_reg16_as_u16(SP), 0x0ffe);
+        assert_eq!(c.memory.lock().unwrap().read(0x0ffe), 0x06);
+        // interrupts are disabled until EI
+        c.execute();
+        assert_eq!(c.code_reg16_as_u16(PC), 0x002d);
+        c.enable_interrupt();
+        c.acc_reg().reg.load(0x08);
+        c.set_interrupt_mask();
+        c.execute();
+        assert_eq!(c.code_reg16_as_u16(PC), 0x003c);
+
+        c.enable_interrupt();
+        c.set_variant(I8080Variant::I8080);
+        c.trigger_rst55();
+        c.execute();
+        assert_eq!(c.code_reg16_as_u16(PC), 0x003d);
+    }
+
+    #[test]
+    fn stop_from_another_thread() {
+        use std::time::Duration;
+        let mut c = I8080Console::default();
+        // JMP 0x0000
+        c.flash(&[0xc3, 0x00, 0x00]);
+        let stop = c.stop_handle();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            stop.store(true, Ordering::Relaxed);
+        });
+        c.run();
+        stopper.join().unwrap();
+        assert!(c.total_cycles() > 0);
+        assert!(!c.stop_handle().load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn immediate_alu_flags() {
+        use I8080RegisterCode::{Acc, Flag};
+        // (opcode, acc, operand, carry, result, flags)
+        let cases = [
+            (0xc6, 0x3a, 0xc6, false, 0x00, 0x57),
+            (0xc6, 0x0f, 0x01, true, 0x10, 0x12),
+            (0xc6, 0xff, 0x01, false, 0x00, 0x57),
+            (0xc6, 0x7f, 0x01, false, 0x80, 0x92),
+            (0xce, 0x3a, 0xc6, true, 0x01, 0x13),
+            (0xce, 0xff, 0x00, true, 0x00, 0x57),
+            (0xce, 0x0f, 0x00, true, 0x10, 0x12),
+            (0xce, 0x7f, 0x00, false, 0x7f, 0x02),
+            (0xd6, 0x3e, 0x3e, false, 0x00, 0x56),
+            (0xd6, 0x12, 0x03, false, 0x0f, 0x06),
+            (0xd6, 0x10, 0x13, true, 0xfd, 0x83),
+            (0xd6, 0x00, 0x01, false, 0xff, 0x87),
+            (0xde, 0x12, 0x03, true, 0x0e, 0x02),
+            (0xde, 0x00, 0x00, true, 0xff, 0x87),
+            (0xde, 0x10, 0x0f, true, 0x00, 0x46),
+            (0xde, 0x80, 0x01, false, 0x7f, 0x02),
+            (0xe6, 0x3a, 0x0f, true, 0x0a, 0x16),
+            (0xe6, 0xf0, 0x0f, true, 0x00, 0x56),
+            (0xe6, 0xff, 0x80, false, 0x80, 0x92),
+            (0xe6, 0x04, 0x03, true, 0x00, 0x46),
+            (0xee, 0x5c, 0x78, true, 0x24, 0x06),
+            (0xee, 0xff, 0xff, true, 0x00, 0x46),
+            (0xee, 0x00, 0x81, false, 0x81, 0x86),
+            (0xf6, 0xb5, 0x0f, true, 0xbf, 0x82),
+            (0xf6, 0x00, 0x00, true, 0x00, 0x46),
+            (0xf6, 0x40, 0x01, false, 0x41, 0x06),
+            (0xfe, 0x0a, 0x05, false, 0x0a, 0x16),
+            (0xfe, 0x02, 0x05, true, 0x02, 0x83),
+            (0xfe, 0x05, 0x05, false, 0x05, 0x56),
+            (0xfe, 0x80, 0x7f, true, 0x80, 0x02),
+        ];
+        for (opcode, acc, operand, carry, res, flags) in cases {
+            let mut c = I8080Console::default();
+            c.flash(&[opcode, operand]);
+            c.code_reg_mut(Acc).reg.load(acc);
+            c.code_reg_mut(Flag).reg.load(0x02 | carry as u8);
+            c.execute();
+            assert_eq!(
+                (c.code_reg_as_u8(Acc), c.code_reg_as_u8(Flag)),
+                (res, flags),
+                "{opcode:02x} {acc:02x} {operand:02x} {carry}"
+            );
+        }
+    }
+
+    #[test]
+    fn current_opcode() {
+        let mut c = I8080Console::default();
+        // MVI B,0x76; MOV A,B
+        c.flash(&[0x06, 0x76, 0x78]);
+        c.execute();
+        assert_eq!(c.current_opcode(), 0x06);
+        c.execute();
+        assert_eq!(c.current_opcode(), 0x78);
+    }
+
+    #[test]
+    fn inr_dcr_memory() {
+        use I8080RegisterCode::Flag;
+        use I8080RegisterCode16::HL;
+        // INR M; DCR M; DCR M
+        let mut c = I8080Console::default();
+        c.flash(&[0x34, 0x35, 0x35]);
+        c.memory.lock().unwrap().write(0x0100, 0xff);
+        c.load_reg16(HL, 0x0100);
+        c.flag_set(StatusFlag::Carry);
+        let before = c.total_cycles();
+        c.execute();
+        assert_eq!(c.total_cycles() - before, 10);
+        assert_eq!(c.memory.lock().unwrap().read(0x0100), 0x00);
+        assert!(c.flag_status().contains(StatusFlag::Zero));
+        assert!(c.flag_status().contains(StatusFlag::Carry));
+        assert_eq!(c.execute(), 10);
+        assert_eq!(c.memory.lock().unwrap().read(0x0100), 0xff);
+        assert!(c.flag_status().contains(StatusFlag::Carry));
+        c.code_reg_mut(Flag).reg.load(0x02);
+        c.execute();
+        assert_eq!(c.memory.lock().unwrap().read(0x0100), 0xfe);
+        assert!(!c.flag_status().contains(StatusFlag::Carry));
+    }
+
+    #[test]
+    fn scratch_clearing() {
+        use I8080RegisterCode::{Acc, PcL, W, Z};
+        use I8080RegisterCode16::{PC, WZ};
+        // LDA 0x0010; JMP 0x0020
+        let mut c = I8080Console::default();
+        c.flash(&[0x3a, 0x10, 0x00, 0xc3, 0x20, 0x00]);
+        c.memory.lock().unwrap().write(0x0010, 0x2a);
+        c.execute();
+        assert_eq!(c.code_reg16_as_u16(WZ), 0x0010);
+        c.set_scratch_clearing(true);
+        c.code_reg_mut(PcL).reg.load(0);
+        c.execute();
+        assert_eq!(c.code_reg_as_u8(Acc), 0x2a);
+        assert_eq!([c.code_reg_as_u8(W), c.code_reg_as_u8(Z)], [0, 0]);
+        c.execute();
+        assert_eq!(c.code_reg16_as_u16(PC), 0x0020);
+        assert_eq!([c.code_reg_as_u8(W), c.code_reg_as_u8(Z)], [0, 0]);
+    }
+
+    #[test]
+    fn timing() {
+        use timing::I8080Timing;
+        // MVI A,1; INX B; MOV B,A; JZ 0x0000; CNZ 0x000b; HLT
+        let program = [
+            0x3e, 0x01, 0x03, 0x47, 0xca, 0x00, 0x00, 0xc4, 0x0b, 0x00, 0x00, 0x76,
+        ];
+        let mut i8080 = I8080Console::default();
+        i8080.flash(&program);
+        i8080.code_reg_mut(I8080RegisterCode::SpL).reg.load(0x80);
+        assert_eq!(
+            [i8080.execute(), i8080.execute(), i8080.execute()],
+            [7, 5, 5]
+        );
+        // not taken, then taken
+        assert_eq!([i8080.execute(), i8080.execute()], [10, 17]);
+        assert_eq!(i8080.execute(), 7);
+        assert_eq!(i8080.total_cycles(), 51);
+
+        // MVI A,0; NOP; CZ 0x0000 (not taken); HLT
+        let mut c = I8080Console::default();
+        c.flash(&[0x3e, 0x00, 0x00, 0xcc, 0x00, 0x00, 0x76]);
+        c.run();
+        assert_eq!(c.total_cycles(), 7 + 4 + 11 + 7);
+
+        let mut i8085 = I8080Console::default();
+        i8085.set_timing(I8080Timing::I8085);
+        i8085.flash(&program);
+        i8085.code_reg_mut(I8080RegisterCode::SpL).reg.load(0x80);
+        i8085.run();
+        assert_eq!(i8085.total_cycles(), 7 + 6 + 4 + 7 + 18 + 5);
+    }
+
+    #[test]
+    fn halt_bus() {
+        use I8080CycleType::*;
+        // MVI A,0x2a; STA 0x0100; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[0x3e, 0x2a, 0x32, 0x00, 0x01, 0x76]);
+        let signals = c.signals();
+        for _ in 0..6 {
+            c.step_cycle();
+        }
+        assert_eq!(c.step_cycle().cycle_type, OpcodeFetch);
+        let halt = CycleInfo {
+            cycle_type: HaltAcknowledge,
+            address: 0x0006,
+            data: 0xff,
+        };
+        for _ in 0..3 {
+            assert_eq!(c.step_cycle(), halt);
+            assert_eq!((c.address_bus.get(), c.regs.bus.get()), (0x0006, 0xff));
+            assert!(signals.halted.get());
+            assert!(!signals.write.get() && !signals.data_in.get());
+        }
+        assert_eq!(c.pc(), 0x0006);
+    }
+
+    #[test]
+    fn step_cycle() {
+        use I8080CycleType::*;
+        let mut c = I8080Console::default();
+        // MOV A,B; MVI A,0x2a; STA 0x0100; PUSH B
+        c.flash(&[0x78, 0x3e, 0x2a, 0x32, 0x00, 0x01, 0xc5]);
+        c.code_reg_mut(I8080RegisterCode::SpL).reg.load(0x10);
+        let mut types = || c.step_cycle().cycle_type;
+        assert_eq!(types(), OpcodeFetch);
+        assert_eq!([types(), types()], [OpcodeFetch, MemoryRead]);
+        assert_eq!(
+            [types(), types(), types(), types()],
+            [OpcodeFetch, MemoryRead, MemoryRead, MemoryWrite]
+        );
+        assert_eq!(
+            [types(), types(), types()],
+            [OpcodeFetch, StackWrite, StackWrite]
+        );
+        let mut c = I8080Console::default();
+        c.flash(&[0x3e, 0x2a, 0x32, 0x00, 0x01]);
+        assert_eq!(c.step_cycle().address, 0x0000);
+        assert_eq!(c.step_cycle().data, 0x2a);
+        c.step_cycle();
+        c.step_cycle();
+        c.step_cycle();
+        let write = c.step_cycle();
+        assert_eq!((write.address, write.data), (0x0100, 0x2a));
+    }
+
+    #[test]
+    fn step_cycle_between_cycles() {
+        let mut c = I8080Console::default();
+        // MVI A,0x2a; STA 0x0100; PUSH B; INR M
+        c.flash(&[0x3e, 0x2a, 0x32, 0x00, 0x01, 0xc5, 0x34]);
+        c.code_reg_mut(I8080RegisterCode::SpL).reg.load(0x10);
+        c.execute();
+        c.step_cycle();
+        c.step_cycle();
+        c.step_cycle();
+        assert_eq!(c.pc(), 0x0005);
+        assert_eq!(c.memory.lock().unwrap().read(0x0100), 0x00);
+        c.step_cycle();
+        assert_eq!(c.memory.lock().unwrap().read(0x0100), 0x2a);
+        assert_eq!(c.total_cycles(), 7 + 13);
+
+        c.step_cycle();
+        c.step_cycle();
+        assert_eq!(c.sp(), 0x000f);
+        assert_eq!(c.total_cycles(), 7 + 13);
+        // the rest of it runs before the next instruction
+        assert_eq!(c.execute(), 11);
+        assert_eq!(c.sp(), 0x000e);
+        assert_eq!(c.pc(), 0x0006);
+        assert_eq!(c.execute(), 10);
+        assert_eq!(c.pc(), 0x0007);
+    }
+
+    #[test]
+    fn stuck_watchdog() {
+        // MVI A,1; ORA A; JNZ 0x0003
+        let program = [0x3e, 0x01, 0xb7, 0xc2, 0x03, 0x00];
+        let mut c = I8080Console::default();
+        c.flash(&program);
+        assert_eq!(c.run_limited(1000), RunResult::LimitReached);
+        c.set_stuck_watchdog(Some(16));
+        assert_eq!(c.run_limited(1000), RunResult::Stuck(0x0003));
+        assert_eq!(c.total_cycles(), 7 + 4 + (998 + 16) * 10);
+
+        // MVI A,1; ORA A; NOP; JNZ 0x0002
+        let mut c = I8080Console::default();
+        c.flash(&[0x3e, 0x01, 0xb7, 0x00, 0xc2, 0x02, 0x00]);
+        c.set_stuck_watchdog(Some(16));
+        assert_eq!(c.run_limited(1000), RunResult::Stuck(0x0002));
+        assert_eq!(c.instruction_count(), 4 + 16);
+
+        // LXI H,0x0100; INR M; JMP 0x0003
+        let mut c = I8080Console::default();
+        c.flash(&[0x21, 0x00, 0x01, 0x34, 0xc3, 0x03, 0x00]);
+        c.set_stuck_watchdog(Some(16));
+        assert_eq!(c.run_limited(1000), RunResult::LimitReached);
+
+        let mut c = I8080Console::default();
+        c.flash(&[0x00, 0x76]);
+        c.set_stuck_watchdog(Some(1));
+        assert_eq!(c.run_limited(1000), RunResult::Halted);
+    }
+
+    #[test]
+    fn run_limited_endless_loop() {
+        // JMP $
+        let mut c = I8080Console::default();
+        c.flash(&[0xc3, 0x00, 0x00]);
+        assert_eq!(c.run_limited(1000), RunResult::LimitReached);
+        assert_eq!(c.instruction_count(), 1000);
+        assert_eq!(c.pc(), 0x0000);
+    }
+
+    #[test]
+    fn step_out() {
+        use I8080RegisterCode::Acc;
+        use I8080RegisterCode16::{PC, SP};
+        let mut c = I8080Console::default();
+        // CALL 0x0010; HLT
+        c.flash(&[0xcd, 0x10, 0x00, 0x76]);
+        let mut memory = c.memory.lock().unwrap();
+        // MVI A,1; CALL 0x0020; INR A; RET
+        for (i, x) in [0x3e, 0x01, 0xcd, 0x20, 0x00, 0x3c, 0xc9]
+            .into_iter()
+            .enumerate()
+        {
+            memory.write(0x0010 + i as u16, x);
+        }
+        // INR A; RET
+        memory.write(0x0020, 0x3c);
+        memory.write(0x0021, 0xc9);
+        drop(memory);
+        c.set_reg16(SP, 0x0100);
+        c.execute();
+        c.execute();
+        assert_eq!(c.reg16(PC), 0x0012);
+        assert_eq!(c.step_out(100), RunResult::Returned);
+        assert_eq!(c.reg16(PC), 0x0003);
+        assert_eq!(c.code_reg_as_u8(Acc), 3);
+        assert_eq!(c.step_out(100), RunResult::Halted);
+
+        // LXI SP,0; CALL 0x0010; HLT, where 0x0010: NOP; RET
+        let mut c = I8080Console::default();
+        c.flash(&[0x31, 0x00, 0x00, 0xcd, 0x10, 0x00, 0x76]);
+        c.memory.lock().unwrap().write(0x0010, 0x00);
+        c.memory.lock().unwrap().write(0x0011, 0xc9);
+        c.execute();
+        c.execute();
+        assert_eq!(c.reg16(SP), 0xfffe);
+        assert_eq!(c.step_out(100), RunResult::Returned);
+        assert_eq!((c.reg16(PC), c.reg16(SP)), (0x0006, 0x0000));
+    }
+
+    #[test]
+    fn interrupt() {
+        use I8080RegisterCode16::{PC, SP};
+        let mut c = I8080Console::<RamB8A16>::default();
+        c.set_reg16(SP, 0x0100);
+        c.set_reg16(PC, 0x1234);
+        c.interrupt(1);
+        assert_eq!(c.reg16(PC), 0x1234);
+        c.enable_interrupt();
+        c.interrupt(1);
+        assert_eq!(c.reg16(PC), 0x0008);
+        assert_eq!(c.reg16(SP), 0x00fe);
+        let memory = c.memory().lock().unwrap();
+        assert_eq!([memory.read(0x00fe), memory.read(0x00ff)], [0x34, 0x12]);
+        drop(memory);
+        assert_eq!(c.total_cycles(), 11);
+        c.interrupt(2);
+        assert_eq!(c.reg16(PC), 0x0008);
+        // only the three bits of RST n reach the opcode
+        c.enable_interrupt();
+        c.interrupt(9);
+        assert_eq!(c.reg16(PC), 0x0008);
+        assert_eq!(c.total_cycles(), 22);
+    }
+
+    #[test]
+    fn tight_loop() {
+        use I8080RegisterCode::*;
+        // MVI D,8; loop: INX B; MOV A,B; ORA C; JNZ loop; DCR D; JNZ loop; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[
+            0x16, 0x08, 0x03, 0x78, 0xb1, 0xc2, 0x02, 0x00, 0x15, 0xc2, 0x02, 0x00, 0x76,
+        ]);
+        c.run();
+        // 2,097,170 instructions
+        assert_eq!(c.current_opcode(), 0x76);
+        assert_eq!([B, C, D, Acc].map(|r| c.code_reg_as_u8(r)), [0; 4]);
+        assert_eq!(c.total_cycles(), 7 + 8 * (65536 * 24 + 15) + 7);
+    }
+
+    #[test]
+    fn breakpoints() {
+        use I8080RegisterCode::B;
+        // MVI B,3; loop: DCR B; JNZ loop; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[0x06, 0x03, 0x05, 0xc2, 0x02, 0x00, 0x76]);
+        c.add_breakpoint(0x0003);
+        assert_eq!(c.run_until_break(), StopReason::Breakpoint(0x0003));
+        assert_eq!(c.register(B), 2);
+        assert_eq!(c.run_until_break(), StopReason::Breakpoint(0x0003));
+        assert_eq!(c.register(B), 1);
+        c.remove_breakpoint(0x0003);
+        assert_eq!(c.run_until_break(), StopReason::Halted);
+        assert_eq!(c.register(B), 0);
+        assert_eq!(c.pc(), 0x0007);
+    }
+
+    #[test]
+    fn fake_clock() {
+        use crate::clock::FakeClock;
+        // MVI A,1; NOP; INX B; CNZ 0x0009; HLT; NOP; RET
+        let mut c = I8080Console::default();
+        c.flash(&[0x3e, 0x01, 0x00, 0x03, 0xc4, 0x09, 0x00, 0x76, 0x00, 0xc9]);
+        c.set_reg16(I8080RegisterCode16::SP, 0x0100);
+        let clock = FakeClock::default();
+        c.set_clock(Box::new(clock.clone()));
+        c.run();
+        assert_eq!(*clock.waits.borrow(), [7, 4, 5, 17, 10, 7]);
+        assert_eq!(c.total_cycles(), 7 + 4 + 5 + 17 + 10 + 7);
+    }
+
+    #[test]
+    fn trace() {
+        use std::cell::RefCell;
+        // MVI A,1; INR A; MOV B,A; HLT
+        let program = [0x3e, 0x01, 0x3c, 0x47, 0x76];
+        let mut c = I8080Console::default();
+        c.flash(&program);
+        let events = Rc::new(RefCell::new(vec![]));
+        let sink = Rc::clone(&events);
+        c.set_trace(Box::new(move |event| sink.borrow_mut().push(event)));
+        c.run();
+        let events = events.borrow();
+        let opcodes = events.iter().map(|e| e.opcode).collect::<Vec<_>>();
+        assert_eq!(opcodes, [0x3e, 0x3c, 0x47, 0x76]);
+        let pcs = events.iter().map(|e| e.pc).collect::<Vec<_>>();
+        assert_eq!(pcs, [0x0000, 0x0002, 0x0003, 0x0004]);
+        // A before INR A and B before MOV B,A
+        assert_eq!((events[1].state.a, events[2].state.a), (1, 2));
+        assert_eq!(events[2].state.b, 0);
+    }
+
+    #[test]
+    fn instruction_count() {
+        // MVI B,5; loop: DCR B; JNZ loop; HLT
+        let mut c = I8080Console::default();
+        c.flash(&[0x06, 0x05, 0x05, 0xc2, 0x02, 0x00, 0x76]);
+        c.run();
+        assert_eq!(c.instruction_count(), 1 + 5 * 2 + 1);
+        c.reset();
+        assert_eq!(c.instruction_count(), 0);
+        c.step();
+        assert_eq!(c.instruction_count(), 1);
+    }
+
+    #[test]
+    fn ring_buffer_polling() {
+        use crate::memory::{MappedMemory, RingBufferDevice};
+        #[rustfmt::skip]
+        let program = [
+            0x26, 0x01,       // MVI H,0x01
+            0x2e, 0x00,       // MVI L,0x00
+            0x06, 0x03,       // MVI B,3
+            0x3a, 0x01, 0x30, // loop: LDA 0x3001
+            0xe6, 0x01,       // ANI 1
+            0xca, 0x06, 0x00, // JZ loop
+            0x3a, 0x00, 0x30, // LDA 0x3000
+            0x77,             // MOV M,A
+            0x23,             // INX H
+            0x05,             // DCR B
+            0xc2, 0x06, 0x00, // JNZ loop
+            0x76,             // HLT
+        ];
+        let mut ring = RingBufferDevice::new(4);
+        let mut c = I8080Console::<MappedMemory>::default();
+        let mut memory = c.memory().lock().unwrap();
+        memory.ram_mut().flash(&program, 0);
+        memory.map(0x3000..0x3002, Box::new(ring.clone()));
+        drop(memory);
+        assert_eq!(c.run_limited(100), RunResult::LimitReached);
+        assert_eq!(c.register(I8080RegisterCode::B), 3);
+        for byte in b"abc" {
+            ring.push(*byte);
+        }
+        assert_eq!(c.run_limited(1000), RunResult::Halted);
+        let memory = c.memory().lock().unwrap();
+        assert_eq!([0x0100, 0x0101, 0x0102].map(|a| memory.read(a)), *b"abc");
+        assert_eq!(memory.read(0x3001), 0);
+    }
+
+    #[test]
+    fn history_over_mapped_memory() {
+        use crate::memory::{MappedMemory, RingBufferDevice};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        #[rustfmt::skip]
+        let program = [
+            0x3e, 0x55,       // MVI A,0x55
+            0x32, 0x00, 0x30, // STA 0x3000
+            0x32, 0x00, 0x01, // STA 0x0100
+            0x32, 0x00, 0x02, // STA 0x0200
+            0x76,             // HLT
+        ];
+        let mut ring = RingBufferDevice::new(4);
+        ring.push(b'a');
+        let watched = Arc::new(AtomicUsize::new(0));
+        let hits = watched.clone();
+        let mut c = I8080Console::<MappedMemory>::default();
+        let mut memory = c.memory().lock().unwrap();
+        memory.ram_mut().flash(&program, 0);
+        memory.ram_mut().set_read_only(0x0200..0x0201);
+        memory.ram_mut().set_write_watch(
+            0x0100,
+            Box::new(move |_, _| {
+                hits.fetch_add(1, Ordering::Relaxed);
+            }),
+        );
+        memory.map(0x3000..0x3002, Box::new(ring));
+        drop(memory);
+        c.enable_history(8);
+        assert_eq!(c.run_limited(10), RunResult::Halted);
+        while c.step_back() {}
+        assert_eq!(c.pc(), 0x0000);
+        let mut memory = c.memory().lock().unwrap();
+        assert_eq!(memory.read(0x0100), 0x00);
+        assert_eq!(watched.load(Ordering::Relaxed), 1);
+        assert_eq!(memory.ram_mut().protected_write_count(), 1);
+        // the store didn't pop the FIFO, and stepping back didn't either
+        assert_eq!(memory.read(0x3001), RingBufferDevice::READY);
+        assert_eq!(memory.read(0x3000), b'a');
+    }
+
+    #[test]
+    fn restart() {
+        use I8080RegisterCode16::{PC, SP};
+        let mut c = I8080Console::<RamB8A16>::default();
+        let mut memory = c.memory().lock().unwrap();
+        memory.flash(&[0xc9], 0x28); // RET
+        memory.flash(&[0xef], 0x0100); // RST 5
+        drop(memory);
+        c.set_reg16(PC, 0x0100);
+        c.set_reg16(SP, 0x0200);
+        c.step();
+        assert_eq!(c.pc(), 0x0028);
+        assert_eq!(c.sp(), 0x01fe);
+        let memory = c.memory().lock().unwrap();
+        assert_eq!([memory.read(0x01fe), memory.read(0x01ff)], [0x01, 0x01]);
+        drop(memory);
+        c.step();
+        assert_eq!((c.pc(), c.sp()), (0x0101, 0x0200));
+    }
 }
@@ -0,0 +1,447 @@
+use super::disasm::{ALU, ALU_IMMEDIATE, CONDITIONS, PAIRS, REGS, STACK_PAIRS};
+use std::collections::HashMap;
+
+const IMPLIED: [(&str, u8); 19] = [
+    ("NOP", 0x00),
+    ("RLC", 0x07),
+    ("RRC", 0x0f),
+    ("RAL", 0x17),
+    ("RAR", 0x1f),
+    ("DAA", 0x27),
+    ("CMA", 0x2f),
+    ("STC", 0x37),
+    ("CMC", 0x3f),
+    ("HLT", 0x76),
+    ("RET", 0xc9),
+    ("PCHL", 0xe9),
+    ("SPHL", 0xf9),
+    ("XCHG", 0xeb),
+    ("XTHL", 0xe3),
+    ("DI", 0xf3),
+    ("EI", 0xfb),
+    // 8085 only, `NOP` on the 8080.
+    ("RIM", 0x20),
+    ("SIM", 0x30),
+];
+
+/// error on assembling 8080 source.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AsmError {
+    /// the mnemonic isn't an 8080 instruction.
+    UnknownMnemonic { line: usize, mnemonic: String },
+    /// the instruction takes a different number of operands.
+    OperandCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// the operand isn't a register, pair or number the instruction accepts.
+    InvalidOperand { line: usize, operand: String },
+    /// the label isn't an identifier.
+    InvalidLabel { line: usize, label: String },
+    /// the label is defined twice.
+    DuplicateLabel { line: usize, label: String },
+    /// the label is never defined.
+    UndefinedLabel { line: usize, label: String },
+}
+
+impl std::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {line}: unknown mnemonic `{mnemonic}`")
+            }
+            AsmError::OperandCount {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: {found} operands where {expected} expected"),
+            AsmError::InvalidOperand { line, operand } => {
+                write!(f, "line {line}: invalid operand `{operand}`")
+            }
+            AsmError::InvalidLabel { line, label } => {
+                write!(f, "line {line}: invalid label `{label}`")
+            }
+            AsmError::DuplicateLabel { line, label } => {
+                write!(f, "line {line}: label `{label}` already defined")
+            }
+            AsmError::UndefinedLabel { line, label } => {
+                write!(f, "line {line}: undefined label `{label}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+enum Immediate {
+    None,
+    Byte(String),
+    Word(String),
+}
+
+struct Instruction {
+    line: usize,
+    opcode: u8,
+    immediate: Immediate,
+}
+
+impl Instruction {
+    fn len(&self) -> u16 {
+        match self.immediate {
+            Immediate::None => 1,
+            Immediate::Byte(_) => 2,
+            Immediate::Word(_) => 3,
+        }
+    }
+}
+
+/// assemble one instruction per line, e.g. `loop: MVI B, 0x0A ; comment`, placed from address 0.
+/// immediates are decimal, `0x1F` or `1Fh`, or a label, which may be defined later.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut instructions = vec![];
+    let mut address = 0u16;
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let mut text = line.split(';').next().unwrap_or_default().trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(AsmError::InvalidLabel {
+                    line: line_no,
+                    label: label.to_string(),
+                });
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(AsmError::DuplicateLabel {
+                    line: line_no,
+                    label: label.to_string(),
+                });
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let instruction = parse(line_no, text)?;
+        address = address.wrapping_add(instruction.len());
+        instructions.push(instruction);
+    }
+    let mut bytes = vec![];
+    for Instruction {
+        line,
+        opcode,
+        immediate,
+    } in instructions
+    {
+        bytes.push(opcode);
+        match immediate {
+            Immediate::None => {}
+            Immediate::Byte(operand) => {
+                let value = value(line, &operand, &labels)?;
+                let byte =
+                    u8::try_from(value).map_err(|_| AsmError::InvalidOperand { line, operand })?;
+                bytes.push(byte)
+            }
+            Immediate::Word(operand) => bytes.extend(value(line, &operand, &labels)?.to_le_bytes()),
+        }
+    }
+    Ok(bytes)
+}
+
+fn is_label(label: &str) -> bool {
+    let mut chars = label.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse(line: usize, text: &str) -> Result<Instruction, AsmError> {
+    let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    let operands: Vec<&str> = match operands.trim() {
+        "" => vec![],
+        operands => operands.split(',').map(str::trim).collect(),
+    };
+    let expect = |expected: usize| {
+        if operands.len() == expected {
+            Ok(&operands[..])
+        } else {
+            Err(AsmError::OperandCount {
+                line,
+                expected,
+                found: operands.len(),
+            })
+        }
+    };
+    let lookup = |table: &[&str], operand: &str| {
+        table
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(operand))
+            .map(|i| i as u8)
+            .ok_or_else(|| AsmError::InvalidOperand {
+                line,
+                operand: operand.to_string(),
+            })
+    };
+    let reg = |operand: &str| lookup(&REGS, operand);
+    let pair = |operand: &str| lookup(&PAIRS, operand);
+    let op = mnemonic.as_str();
+    let (opcode, immediate) =
+        if let Some(&(_, opcode)) = IMPLIED.iter().find(|(name, _)| *name == op) {
+            expect(0)?;
+            (opcode, Immediate::None)
+        } else if let Some(n) = ALU.iter().position(|name| *name == op) {
+            let src = expect(1)?[0];
+            (0x80 | (n as u8) << 3 | reg(src)?, Immediate::None)
+        } else if let Some(n) = ALU_IMMEDIATE.iter().position(|name| *name == op) {
+            let data = expect(1)?[0];
+            (0xc6 | (n as u8) << 3, Immediate::Byte(data.to_string()))
+        } else {
+            match op {
+                "MOV" => {
+                    let args = expect(2)?;
+                    let (dst, src) = (reg(args[0])?, reg(args[1])?);
+                    if (dst, src) == (6, 6) {
+                        return Err(AsmError::InvalidOperand {
+                            line,
+                            operand: "M".to_string(),
+                        });
+                    }
+                    (0x40 | dst << 3 | src, Immediate::None)
+                }
+                "MVI" => {
+                    let args = expect(2)?;
+                    (
+                        0x06 | reg(args[0])? << 3,
+                        Immediate::Byte(args[1].to_string()),
+                    )
+                }
+                "INR" | "DCR" => {
+                    let dst = expect(1)?[0];
+                    let base = if op == "INR" { 0x04 } else { 0x05 };
+                    (base | reg(dst)? << 3, Immediate::None)
+                }
+                "LXI" => {
+                    let args = expect(2)?;
+                    (
+                        0x01 | pair(args[0])? << 4,
+                        Immediate::Word(args[1].to_string()),
+                    )
+                }
+                "DAD" | "INX" | "DCX" => {
+                    let rp = expect(1)?[0];
+                    let base = match op {
+                        "DAD" => 0x09,
+                        "INX" => 0x03,
+                        _ => 0x0b,
+                    };
+                    (base | pair(rp)? << 4, Immediate::None)
+                }
+                "STAX" | "LDAX" => {
+                    let rp = expect(1)?[0];
+                    let base = if op == "STAX" { 0x02 } else { 0x0a };
+                    (base | lookup(&PAIRS[..2], rp)? << 4, Immediate::None)
+                }
+                "PUSH" | "POP" => {
+                    let rp = expect(1)?[0];
+                    let base = if op == "PUSH" { 0xc5 } else { 0xc1 };
+                    (base | lookup(&STACK_PAIRS, rp)? << 4, Immediate::None)
+                }
+                "SHLD" | "LHLD" | "STA" | "LDA" | "JMP" | "CALL" => {
+                    let address = expect(1)?[0];
+                    let opcode = match op {
+                        "SHLD" => 0x22,
+                        "LHLD" => 0x2a,
+                        "STA" => 0x32,
+                        "LDA" => 0x3a,
+                        "JMP" => 0xc3,
+                        _ => 0xcd,
+                    };
+                    (opcode, Immediate::Word(address.to_string()))
+                }
+                "IN" | "OUT" => {
+                    let port = expect(1)?[0];
+                    let opcode = if op == "IN" { 0xdb } else { 0xd3 };
+                    (opcode, Immediate::Byte(port.to_string()))
+                }
+                "RST" => {
+                    let n = expect(1)?[0];
+                    match number(n) {
+                        Some(n @ 0..=7) => (0xc7 | (n as u8) << 3, Immediate::None),
+                        _ => {
+                            return Err(AsmError::InvalidOperand {
+                                line,
+                                operand: n.to_string(),
+                            })
+                        }
+                    }
+                }
+                _ => {
+                    let condition = op
+                        .get(1..)
+                        .and_then(|cc| CONDITIONS.iter().position(|name| *name == cc));
+                    match (op.get(..1), condition) {
+                        (Some("J"), Some(cc)) => {
+                            let address = expect(1)?[0];
+                            (0xc2 | (cc as u8) << 3, Immediate::Word(address.to_string()))
+                        }
+                        (Some("C"), Some(cc)) => {
+                            let address = expect(1)?[0];
+                            (0xc4 | (cc as u8) << 3, Immediate::Word(address.to_string()))
+                        }
+                        (Some("R"), Some(cc)) => {
+                            expect(0)?;
+                            (0xc0 | (cc as u8) << 3, Immediate::None)
+                        }
+                        _ => {
+                            return Err(AsmError::UnknownMnemonic {
+                                line,
+                                mnemonic: mnemonic.clone(),
+                            })
+                        }
+                    }
+                }
+            }
+        };
+    Ok(Instruction {
+        line,
+        opcode,
+        immediate,
+    })
+}
+
+/// a number as decimal, `0x1F` or `1Fh`, or `None` if it isn't one.
+fn number(text: &str) -> Option<u16> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(hex) = text.strip_suffix(['h', 'H']) {
+        u16::from_str_radix(hex, 16).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+fn value(line: usize, operand: &str, labels: &HashMap<String, u16>) -> Result<u16, AsmError> {
+    if operand.starts_with(|c: char| c.is_ascii_digit()) {
+        number(operand).ok_or_else(|| AsmError::InvalidOperand {
+            line,
+            operand: operand.to_string(),
+        })
+    } else if is_label(operand) {
+        labels
+            .get(operand)
+            .copied()
+            .ok_or_else(|| AsmError::UndefinedLabel {
+                line,
+                label: operand.to_string(),
+            })
+    } else {
+        Err(AsmError::InvalidOperand {
+            line,
+            operand: operand.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{Memory, RamB8A16};
+    use crate::processor::i8080::disasm::disassemble;
+    use crate::processor::i8080::{I8080Console, I8080RegisterCode};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn assemble_loop() {
+        let source = "
+                    JMP start     ; forward reference
+            start:  MVI B, 5
+                    mvi a, 0
+            loop:   ADI 0x03
+                    DCR B
+                    JNZ loop
+                    STA 0100h
+                    HLT
+        ";
+        let bytes = assemble(source).unwrap();
+        assert_eq!(
+            bytes,
+            [
+                0xc3, 0x03, 0x00, 0x06, 0x05, 0x3e, 0x00, 0xc6, 0x03, 0x05, 0xc2, 0x07, 0x00, 0x32,
+                0x00, 0x01, 0x76
+            ]
+        );
+        let mut memory = RamB8A16::new();
+        memory.flash(&bytes, 0);
+        let mut c = I8080Console {
+            memory: Arc::new(Mutex::new(memory)),
+            ..Default::default()
+        };
+        c.run();
+        assert_eq!(c.register(I8080RegisterCode::Acc), 15);
+        assert_eq!(c.memory().lock().unwrap().read(0x0100), 15);
+    }
+
+    #[test]
+    fn disassembly_reassembles() {
+        for opcode in 0..=255u8 {
+            let bytes = [opcode, 0x34, 0x12];
+            let (text, len) = disassemble(&bytes).unwrap();
+            // undocumented opcodes disassemble as their documented twins
+            let reassembled = assemble(&text).unwrap();
+            assert_eq!(reassembled.len(), len, "{text}");
+            assert_eq!(reassembled[1..], bytes[1..len], "{text}");
+            assert_eq!(disassemble(&reassembled).unwrap().0, text);
+        }
+    }
+
+    #[test]
+    fn assemble_errors() {
+        assert_eq!(
+            assemble("NOP\nFOO A"),
+            Err(AsmError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "FOO".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("MOV A"),
+            Err(AsmError::OperandCount {
+                line: 1,
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            assemble("PUSH SP"),
+            Err(AsmError::InvalidOperand {
+                line: 1,
+                operand: "SP".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("MVI A, 256"),
+            Err(AsmError::InvalidOperand {
+                line: 1,
+                operand: "256".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("x: NOP\nx: NOP"),
+            Err(AsmError::DuplicateLabel {
+                line: 2,
+                label: "x".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("NOP\n\nJMP nowhere"),
+            Err(AsmError::UndefinedLabel {
+                line: 3,
+                label: "nowhere".to_string()
+            })
+        );
+    }
+}
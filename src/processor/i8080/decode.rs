@@ -0,0 +1,145 @@
+use super::{I8080JumpCondition, I8080RegisterCode, I8080RegisterCode16};
+pub use crate::decode::{decode, IndirectPair, Instruction, Operand, Pair, Reg8, StackPair};
+
+impl From<Reg8> for I8080RegisterCode {
+    fn from(reg: Reg8) -> Self {
+        use I8080RegisterCode::*;
+        [B, C, D, E, H, L, Acc, Acc][reg as usize]
+    }
+}
+
+impl From<Pair> for I8080RegisterCode16 {
+    fn from(pair: Pair) -> Self {
+        use I8080RegisterCode16::*;
+        [BC, DE, HL, SP][pair as usize]
+    }
+}
+
+impl From<StackPair> for I8080RegisterCode16 {
+    fn from(pair: StackPair) -> Self {
+        use I8080RegisterCode16::*;
+        [BC, DE, HL, PSW][pair as usize]
+    }
+}
+
+impl From<IndirectPair> for I8080RegisterCode16 {
+    fn from(pair: IndirectPair) -> Self {
+        use I8080RegisterCode16::*;
+        [BC, DE][pair as usize]
+    }
+}
+
+/// the opcode of a conditional instruction, or `unconditional` for `Anytime`.
+fn conditional(cond: I8080JumpCondition, base: u8, unconditional: u8) -> u8 {
+    cond.bits().map_or(unconditional, |bits| base | bits << 3)
+}
+
+impl Instruction {
+    /// the bytes of the instruction, the documented opcode followed by the operands.
+    pub fn encode(self) -> Vec<u8> {
+        use Instruction::*;
+        let rp = |bits: u8| bits << 4;
+        let reg = Operand::bits;
+        let alu = |base: u8, operand| vec![base | reg(operand)];
+        let with_word = |opcode: u8, word: u16| {
+            let [low, high] = word.to_le_bytes();
+            vec![opcode, low, high]
+        };
+        match self {
+            Nop => vec![0x00],
+            Lxi(pair, word) => with_word(0x01 | rp(pair as u8), word),
+            Stax(pair) => vec![0x02 | rp(pair as u8)],
+            Ldax(pair) => vec![0x0a | rp(pair as u8)],
+            Shld(word) => with_word(0x22, word),
+            Lhld(word) => with_word(0x2a, word),
+            Sta(word) => with_word(0x32, word),
+            Lda(word) => with_word(0x3a, word),
+            Inx(pair) => vec![0x03 | rp(pair as u8)],
+            Dcx(pair) => vec![0x0b | rp(pair as u8)],
+            Dad(pair) => vec![0x09 | rp(pair as u8)],
+            Inr(operand) => vec![0x04 | reg(operand) << 3],
+            Dcr(operand) => vec![0x05 | reg(operand) << 3],
+            Mvi(operand, byte) => vec![0x06 | reg(operand) << 3, byte],
+            Rlc => vec![0x07],
+            Rrc => vec![0x0f],
+            Ral => vec![0x17],
+            Rar => vec![0x1f],
+            Daa => vec![0x27],
+            Cma => vec![0x2f],
+            Stc => vec![0x37],
+            Cmc => vec![0x3f],
+            Rim => vec![0x20],
+            Sim => vec![0x30],
+            Mov(to, from) => vec![0x40 | reg(to) << 3 | reg(from)],
+            Hlt => vec![0x76],
+            Add(operand) => alu(0x80, operand),
+            Adc(operand) => alu(0x88, operand),
+            Sub(operand) => alu(0x90, operand),
+            Sbb(operand) => alu(0x98, operand),
+            Ana(operand) => alu(0xa0, operand),
+            Xra(operand) => alu(0xa8, operand),
+            Ora(operand) => alu(0xb0, operand),
+            Cmp(operand) => alu(0xb8, operand),
+            Adi(byte) => vec![0xc6, byte],
+            Aci(byte) => vec![0xce, byte],
+            Sui(byte) => vec![0xd6, byte],
+            Sbi(byte) => vec![0xde, byte],
+            Ani(byte) => vec![0xe6, byte],
+            Xri(byte) => vec![0xee, byte],
+            Ori(byte) => vec![0xf6, byte],
+            Cpi(byte) => vec![0xfe, byte],
+            Ret(cond) => vec![conditional(cond, 0xc0, 0xc9)],
+            Jmp(cond, word) => with_word(conditional(cond, 0xc2, 0xc3), word),
+            Call(cond, word) => with_word(conditional(cond, 0xc4, 0xcd), word),
+            Pop(pair) => vec![0xc1 | rp(pair as u8)],
+            Push(pair) => vec![0xc5 | rp(pair as u8)],
+            Pchl => vec![0xe9],
+            Sphl => vec![0xf9],
+            Out(byte) => vec![0xd3, byte],
+            In(byte) => vec![0xdb, byte],
+            Xthl => vec![0xe3],
+            Xchg => vec![0xeb],
+            Di => vec![0xf3],
+            Ei => vec![0xfb],
+            Rst(n) => vec![0xc7 | (n & 0x7) << 3],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::i8080::disasm::disassemble;
+
+    #[test]
+    fn encode_pairs() {
+        use Instruction::*;
+        assert_eq!(Lxi(Pair::SP, 0x1234).encode(), [0x31, 0x34, 0x12]);
+        assert_eq!(Push(StackPair::PSW).encode(), [0xf5]);
+        assert_eq!(Pop(StackPair::PSW).encode(), [0xf1]);
+        assert_eq!(Inx(Pair::SP).encode(), [0x33]);
+        assert_eq!(Stax(IndirectPair::DE).encode(), [0x12]);
+        assert_eq!(Ldax(IndirectPair::BC).encode(), [0x0a]);
+        assert_eq!(
+            Mov(Operand::Reg(Reg8::A), Operand::Reg(Reg8::L)).encode(),
+            [0x7d]
+        );
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let undocumented = [0x08, 0x10, 0x18, 0x28, 0x38, 0xcb, 0xd9, 0xdd, 0xed, 0xfd];
+        for opcode in 0..=255u8 {
+            let bytes = [opcode, 0x34, 0x12];
+            let (inst, len) = decode(&bytes).unwrap();
+            let encoded = inst.encode();
+            assert_eq!(disassemble(&encoded), disassemble(&bytes), "{opcode:#04x}");
+            assert_eq!(encoded.len(), len, "{opcode:#04x}");
+            if undocumented.contains(&opcode) {
+                assert_eq!(decode(&encoded), Some((inst, len)), "{opcode:#04x}");
+            } else {
+                assert_eq!(encoded, bytes[..len], "{opcode:#04x}");
+            }
+        }
+    }
+}
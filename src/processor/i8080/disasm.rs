@@ -0,0 +1,206 @@
+use super::I8080JumpCondition;
+use crate::decode::{decode, Instruction, Operand};
+use std::collections::HashMap;
+
+pub(super) const REGS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "M", "A"];
+pub(super) const PAIRS: [&str; 4] = ["B", "D", "H", "SP"];
+pub(super) const STACK_PAIRS: [&str; 4] = ["B", "D", "H", "PSW"];
+pub(super) const CONDITIONS: [&str; 8] = ["NZ", "Z", "NC", "C", "PO", "PE", "P", "M"];
+pub(super) const ALU: [&str; 8] = ["ADD", "ADC", "SUB", "SBB", "ANA", "XRA", "ORA", "CMP"];
+pub(super) const ALU_IMMEDIATE: [&str; 8] =
+    ["ADI", "ACI", "SUI", "SBI", "ANI", "XRI", "ORI", "CPI"];
+
+/// what follows the mnemonic and its registers.
+enum Immediate {
+    None,
+    Byte(u8),
+    Word(u16),
+    /// the address a jump or call goes to.
+    Target(u16),
+}
+
+/// decode the instruction at the head of `bytes` into its mnemonic, e.g. `"MVI B, 0x0A"`,
+/// and its length, or `None` if `bytes` is empty. missing operand bytes read as 0.
+/// undocumented opcodes are named after the instruction they behave as, as [`decode`]
+/// names them, and 0x20 and 0x30 as `RIM` and `SIM`.
+pub fn disassemble(bytes: &[u8]) -> Option<(String, usize)> {
+    disassemble_with_symbols(bytes, &HashMap::new())
+}
+
+/// [`disassemble`] naming the targets of jumps and calls found in `symbols`, e.g.
+/// `"CALL BDOS"`. other targets stay hex. branches of the 8080 are all absolute.
+pub fn disassemble_with_symbols(
+    bytes: &[u8],
+    symbols: &HashMap<u16, String>,
+) -> Option<(String, usize)> {
+    use Instruction::*;
+    let (inst, len) = decode(bytes)?;
+    let reg = |operand: Operand| REGS[operand.bits() as usize];
+    let condition = |cond: I8080JumpCondition| cond.bits().map(|bits| CONDITIONS[bits as usize]);
+    let alu = |n: usize, operand| (format!("{} {}", ALU[n], reg(operand)), Immediate::None);
+    let alu_immediate = |n: usize, byte| (ALU_IMMEDIATE[n].to_string(), Immediate::Byte(byte));
+    let plain = |text: &str| (text.to_string(), Immediate::None);
+    let (text, immediate) = match inst {
+        Nop => plain("NOP"),
+        Lxi(pair, word) => (
+            format!("LXI {}", PAIRS[pair as usize]),
+            Immediate::Word(word),
+        ),
+        Stax(pair) => plain(&format!("STAX {}", PAIRS[pair as usize])),
+        Ldax(pair) => plain(&format!("LDAX {}", PAIRS[pair as usize])),
+        Shld(word) => ("SHLD".to_string(), Immediate::Word(word)),
+        Lhld(word) => ("LHLD".to_string(), Immediate::Word(word)),
+        Sta(word) => ("STA".to_string(), Immediate::Word(word)),
+        Lda(word) => ("LDA".to_string(), Immediate::Word(word)),
+        Inx(pair) => plain(&format!("INX {}", PAIRS[pair as usize])),
+        Dcx(pair) => plain(&format!("DCX {}", PAIRS[pair as usize])),
+        Dad(pair) => plain(&format!("DAD {}", PAIRS[pair as usize])),
+        Inr(operand) => plain(&format!("INR {}", reg(operand))),
+        Dcr(operand) => plain(&format!("DCR {}", reg(operand))),
+        Mvi(operand, byte) => (format!("MVI {}", reg(operand)), Immediate::Byte(byte)),
+        Rlc => plain("RLC"),
+        Rrc => plain("RRC"),
+        Ral => plain("RAL"),
+        Rar => plain("RAR"),
+        Daa => plain("DAA"),
+        Cma => plain("CMA"),
+        Stc => plain("STC"),
+        Cmc => plain("CMC"),
+        Rim => plain("RIM"),
+        Sim => plain("SIM"),
+        Mov(to, from) => plain(&format!("MOV {}, {}", reg(to), reg(from))),
+        Hlt => plain("HLT"),
+        Add(operand) => alu(0, operand),
+        Adc(operand) => alu(1, operand),
+        Sub(operand) => alu(2, operand),
+        Sbb(operand) => alu(3, operand),
+        Ana(operand) => alu(4, operand),
+        Xra(operand) => alu(5, operand),
+        Ora(operand) => alu(6, operand),
+        Cmp(operand) => alu(7, operand),
+        Adi(byte) => alu_immediate(0, byte),
+        Aci(byte) => alu_immediate(1, byte),
+        Sui(byte) => alu_immediate(2, byte),
+        Sbi(byte) => alu_immediate(3, byte),
+        Ani(byte) => alu_immediate(4, byte),
+        Xri(byte) => alu_immediate(5, byte),
+        Ori(byte) => alu_immediate(6, byte),
+        Cpi(byte) => alu_immediate(7, byte),
+        Ret(cond) => match condition(cond) {
+            Some(cc) => plain(&format!("R{cc}")),
+            None => plain("RET"),
+        },
+        Jmp(cond, word) => match condition(cond) {
+            Some(cc) => (format!("J{cc}"), Immediate::Target(word)),
+            None => ("JMP".to_string(), Immediate::Target(word)),
+        },
+        Call(cond, word) => match condition(cond) {
+            Some(cc) => (format!("C{cc}"), Immediate::Target(word)),
+            None => ("CALL".to_string(), Immediate::Target(word)),
+        },
+        Pop(pair) => plain(&format!("POP {}", STACK_PAIRS[pair as usize])),
+        Push(pair) => plain(&format!("PUSH {}", STACK_PAIRS[pair as usize])),
+        Pchl => plain("PCHL"),
+        Sphl => plain("SPHL"),
+        Out(byte) => ("OUT".to_string(), Immediate::Byte(byte)),
+        In(byte) => ("IN".to_string(), Immediate::Byte(byte)),
+        Xthl => plain("XTHL"),
+        Xchg => plain("XCHG"),
+        Di => plain("DI"),
+        Ei => plain("EI"),
+        Rst(n) => plain(&format!("RST {}", n & 0x7)),
+    };
+    let separator = if text.contains(' ') { ", " } else { " " };
+    let text = match immediate {
+        Immediate::None => text,
+        Immediate::Byte(byte) => format!("{text}{separator}0x{byte:02X}"),
+        Immediate::Target(word) if symbols.contains_key(&word) => {
+            format!("{text}{separator}{}", symbols[&word])
+        }
+        Immediate::Word(word) | Immediate::Target(word) => {
+            format!("{text}{separator}0x{word:04X}")
+        }
+    };
+    Some((text, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassemble_program() {
+        let program = [
+            0x31, 0x00, 0x01, // LXI SP, 0x0100
+            0x06, 0x0a, // MVI B, 0x0A
+            0x78, // MOV A, B
+            0x86, // ADD M
+            0x05, // DCR B
+            0xc2, 0x34, 0x12, // JNZ 0x1234
+            0xf5, // PUSH PSW
+            0xe6, 0x0f, // ANI 0x0F
+            0xd3, 0x01, // OUT 0x01
+            0xcd, 0x00, 0x20, // CALL 0x2000
+            0x0a, // LDAX B
+            0xef, // RST 5
+            0x76, // HLT
+        ];
+        let expected = [
+            ("LXI SP, 0x0100", 3),
+            ("MVI B, 0x0A", 2),
+            ("MOV A, B", 1),
+            ("ADD M", 1),
+            ("DCR B", 1),
+            ("JNZ 0x1234", 3),
+            ("PUSH PSW", 1),
+            ("ANI 0x0F", 2),
+            ("OUT 0x01", 2),
+            ("CALL 0x2000", 3),
+            ("LDAX B", 1),
+            ("RST 5", 1),
+            ("HLT", 1),
+        ];
+        let mut at = 0;
+        for (text, len) in expected {
+            assert_eq!(disassemble(&program[at..]), Some((text.to_string(), len)));
+            at += len;
+        }
+        assert_eq!(at, program.len());
+    }
+
+    #[test]
+    fn disassemble_as_decoded() {
+        assert_eq!(disassemble(&[0x20]), Some(("RIM".to_string(), 1)));
+        assert_eq!(disassemble(&[0x30]), Some(("SIM".to_string(), 1)));
+        assert_eq!(disassemble(&[0x08]), Some(("NOP".to_string(), 1)));
+        assert_eq!(
+            disassemble(&[0xfd, 0x34, 0x12]),
+            Some(("CALL 0x1234".to_string(), 3))
+        );
+    }
+
+    #[test]
+    fn disassemble_empty() {
+        assert_eq!(disassemble(&[]), None);
+        assert_eq!(disassemble_with_symbols(&[], &HashMap::new()), None);
+    }
+
+    #[test]
+    fn disassemble_symbols() {
+        let symbols = HashMap::from([(0x0005, "BDOS".to_string()), (0x0100, "START".to_string())]);
+        let cases: [(&[u8], &str); 5] = [
+            (&[0xcd, 0x05, 0x00], "CALL BDOS"),
+            (&[0xca, 0x00, 0x01], "JZ START"),
+            (&[0xc3, 0x06, 0x00], "JMP 0x0006"),
+            // only branch targets are named
+            (&[0x21, 0x05, 0x00], "LXI H, 0x0005"),
+            (&[0x3a, 0x00, 0x01], "LDA 0x0100"),
+        ];
+        for (bytes, text) in cases {
+            assert_eq!(
+                disassemble_with_symbols(bytes, &symbols),
+                Some((text.to_string(), 3))
+            );
+        }
+    }
+}
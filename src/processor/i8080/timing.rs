@@ -0,0 +1,97 @@
+/// T-states spent by each opcode, which slightly differ among the 8080 family.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct I8080Timing {
+    /// states of each opcode. conditional ones are of the not-taken case.
+    states: [u8; 256],
+    /// states of each opcode whose condition is satisfied.
+    taken_states: [u8; 256],
+}
+
+#[rustfmt::skip]
+const I8080_STATES: [u8; 256] = [
+//  0   1   2   3   4   5   6   7   8   9   A   B   C   D   E   F
+    4, 10,  7,  5,  5,  5,  7,  4,  4, 10,  7,  5,  5,  5,  7,  4, // 0
+    4, 10,  7,  5,  5,  5,  7,  4,  4, 10,  7,  5,  5,  5,  7,  4, // 1
+    4, 10, 16,  5,  5,  5,  7,  4,  4, 10, 16,  5,  5,  5,  7,  4, // 2
+    4, 10, 13,  5, 10, 10, 10,  4,  4, 10, 13,  5,  5,  5,  7,  4, // 3
+    5,  5,  5,  5,  5,  5,  7,  5,  5,  5,  5,  5,  5,  5,  7,  5, // 4
+    5,  5,  5,  5,  5,  5,  7,  5,  5,  5,  5,  5,  5,  5,  7,  5, // 5
+    5,  5,  5,  5,  5,  5,  7,  5,  5,  5,  5,  5,  5,  5,  7,  5, // 6
+    7,  7,  7,  7,  7,  7,  7,  7,  5,  5,  5,  5,  5,  5,  7,  5, // 7
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // 8
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // 9
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // A
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // B
+    5, 10, 10, 10, 11, 11,  7, 11,  5, 10, 10, 10, 11, 17,  7, 11, // C
+    5, 10, 10, 10, 11, 11,  7, 11,  5, 10, 10, 10, 11, 17,  7, 11, // D
+    5, 10, 10, 18, 11, 11,  7, 11,  5,  5, 10,  4, 11, 17,  7, 11, // E
+    5, 10, 10,  4, 11, 11,  7, 11,  5,  5, 10,  4, 11, 17,  7, 11, // F
+];
+
+/// undocumented opcodes are counted as the 8080 instructions they alias.
+#[rustfmt::skip]
+const I8085_STATES: [u8; 256] = [
+//  0   1   2   3   4   5   6   7   8   9   A   B   C   D   E   F
+    4, 10,  7,  6,  4,  4,  7,  4,  4, 10,  7,  6,  4,  4,  7,  4, // 0
+    4, 10,  7,  6,  4,  4,  7,  4,  4, 10,  7,  6,  4,  4,  7,  4, // 1
+    4, 10, 16,  6,  4,  4,  7,  4,  4, 10, 16,  6,  4,  4,  7,  4, // 2
+    4, 10, 13,  6, 10, 10, 10,  4,  4, 10, 13,  6,  4,  4,  7,  4, // 3
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // 4
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // 5
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // 6
+    7,  7,  7,  7,  7,  7,  5,  7,  4,  4,  4,  4,  4,  4,  7,  4, // 7
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // 8
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // 9
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // A
+    4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4, // B
+    6, 10,  7, 10,  9, 12,  7, 12,  6, 10,  7, 10,  9, 18,  7, 12, // C
+    6, 10,  7, 10,  9, 12,  7, 12,  6, 10,  7, 10,  9, 18,  7, 12, // D
+    6, 10,  7, 16,  9, 12,  7, 12,  6,  6,  7,  4,  9, 18,  7, 12, // E
+    6, 10,  7,  4,  9, 12,  7, 12,  6,  6,  7,  4,  9, 18,  7, 12, // F
+];
+
+/// overwrite the states of conditional `RET`, `JMP` and `CALL` with taken ones.
+const fn taken(mut states: [u8; 256], ret: u8, jump: u8, call: u8) -> [u8; 256] {
+    let mut cond = 0;
+    while cond < 8 {
+        let opcode = 0xc0 | (cond << 3);
+        states[opcode] = ret;
+        states[opcode | 2] = jump;
+        states[opcode | 4] = call;
+        cond += 1;
+    }
+    states
+}
+
+impl I8080Timing {
+    pub const I8080: Self = Self {
+        states: I8080_STATES,
+        taken_states: taken(I8080_STATES, 11, 10, 17),
+    };
+    pub const I8085: Self = Self {
+        states: I8085_STATES,
+        taken_states: taken(I8085_STATES, 12, 10, 18),
+    };
+
+    pub fn new(states: [u8; 256], taken_states: [u8; 256]) -> Self {
+        Self {
+            states,
+            taken_states,
+        }
+    }
+
+    /// states of `opcode`. `taken` tells whether its condition was satisfied.
+    pub fn states(&self, opcode: u8, taken: bool) -> u8 {
+        if taken {
+            self.taken_states[opcode as usize]
+        } else {
+            self.states[opcode as usize]
+        }
+    }
+}
+
+impl Default for I8080Timing {
+    fn default() -> Self {
+        Self::I8080
+    }
+}
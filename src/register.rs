@@ -4,7 +4,7 @@ pub trait Register: Sized {
     type Size: BitsSignal;
     fn read(&self) -> Self::Size;
     fn load(&mut self, bits: Self::Size);
-    fn masked(&mut self, mask: Self::Size) -> MaskedRegister<Self> {
+    fn masked(&mut self, mask: Self::Size) -> MaskedRegister<'_, Self> {
         MaskedRegister { reg: self, mask }
     }
 }
@@ -33,10 +33,35 @@ impl<'a, R: Register> Register for MaskedRegister<'a, R> {
     }
 }
 
+/// a register of any width `T`, e.g. `RegisterN<u16>` for a 16-bit one.
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegisterN<T: BitsSignal> {
+    bits: T,
+}
+
+impl<T: BitsSignal> RegisterN<T> {
+    pub fn new(bits: T) -> Self {
+        Self { bits }
+    }
+}
+
+impl<T: BitsSignal> Register for RegisterN<T> {
+    type Size = T;
+
+    fn read(&self) -> Self::Size {
+        self.bits
+    }
+    fn load(&mut self, bits: Self::Size) {
+        self.bits = bits
+    }
+}
+
 pub mod bit8 {
     use crate::register::Register;
 
     #[derive(Debug, Clone, Eq, PartialEq, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Register8 {
         bits: u8,
     }
@@ -48,6 +73,7 @@ pub mod bit8 {
     }
 
     #[derive(Debug, Clone, Eq, PartialEq, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Register8Pair {
         h: Register8,
         l: Register8,
@@ -64,7 +90,7 @@ pub mod bit8 {
             &mut self.h
         }
         pub fn l_mut(&mut self) -> &mut Register8 {
-            &mut self.h
+            &mut self.l
         }
         pub fn increment(&mut self) {
             let hl = self.as_u16();
@@ -86,7 +112,6 @@ pub mod bit8 {
     impl Register for Register8 {
         type Size = u8;
 
-        #[must_use]
         fn read(&self) -> Self::Size {
             self.bits
         }
@@ -130,6 +155,15 @@ pub mod bit8 {
             assert_eq!(l.read(), 69);
         }
 
+        #[test]
+        fn reg8_pair_halves() {
+            let mut reg16 = Register8Pair::new(Register8::new(0x12), Register8::new(0x34));
+            reg16.l_mut().load(0x56);
+            assert_eq!(reg16.read(), 0x1256);
+            reg16.h_mut().load(0x78);
+            assert_eq!(reg16.read(), 0x7856);
+        }
+
         #[test]
         fn reg8_flag_reg() {
             let mut reg = Register8::default();
@@ -144,3 +178,28 @@ pub mod bit8 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reg_n() {
+        let mut reg16 = RegisterN::new(0x1234u16);
+        assert_eq!(reg16.read(), 0x1234);
+        reg16.load(0xbeef);
+        assert_eq!(reg16.read(), 0xbeef);
+        let mut masked = reg16.masked(0xff00);
+        masked.load(0x1234);
+        assert_eq!(masked.read(), 0x1200);
+        assert_eq!(masked.unmasked().read(), 0x12ef);
+
+        let mut reg32 = RegisterN::<u32>::default();
+        assert_eq!(reg32.read(), 0);
+        reg32.load(0xdead_beef);
+        assert_eq!(reg32.read(), 0xdead_beef);
+        let mut masked = reg32.masked(0x0000_ffff);
+        masked.load(0x1234_5678);
+        assert_eq!(masked.unmasked().read(), 0xdead_5678);
+    }
+}
@@ -0,0 +1,24 @@
+//! builds against the crate without `std` too, e.g. `cargo test --no-default-features`.
+
+use n800::alu::bit8::Adder;
+use n800::alu::{StatusFlag, ALU};
+use n800::register::bit8::Register8;
+use n800::register::Register;
+
+#[test]
+fn add_without_std() {
+    let mut acc = Register8::new(0x3a);
+    let (sum, flags) = Adder::adder().op(acc.read(), 0xc6);
+    acc.load(sum);
+    assert_eq!(acc.read(), 0x00);
+    assert!(flags.contains(StatusFlag::Zero) && flags.contains(StatusFlag::Carry));
+}
+
+#[test]
+fn decode_without_std() {
+    use n800::decode::{decode, Instruction, Operand, Reg8};
+    assert_eq!(
+        decode(&[0x3e, 0x2a]),
+        Some((Instruction::Mvi(Operand::Reg(Reg8::A), 0x2a), 2))
+    );
+}